@@ -6,15 +6,25 @@
 mod state;
 
 use rock_paper_scissors::{ApplicationParameters, GameMessage, Operation, RockPaperScissorsAbi, 
-    GameRoom, LeaderboardEntry, Choice};
+    GameRoom, LeaderboardEntry, Choice, RoomStatus, RoundResult, GameError, OperationOutcome,
+    OperationResponse, RejectedAction, EloConfig, LeaderboardOrder, LEADERBOARD_SIZE, MatchFormat, QueueEntry, Series, Stake, Currency, TimeoutPenalty, validate_commitment, validate_player_name,
+    validate_room_alias, room_code, normalize_room_code, invite_code_hash, RoomAccess, DEFAULT_MOVE_TIMEOUT_SECS};
+use rock_paper_scissors::league::{League, LeagueFormat, LeagueStatus, MAX_LEAGUE_PLAYERS, MIN_LEAGUE_PLAYERS};
+use rock_paper_scissors::rewards::RewardBalance;
+use rock_paper_scissors::tournament::{self, SeedingMethod, Tournament, TournamentFormat, TournamentStatus, MAX_TOURNAMENT_PLAYERS,
+    MIN_TOURNAMENT_PLAYERS};
 use linera_sdk::{
-    linera_base_types::{ChainId, WithContractAbi},
+    abis::fungible::FungibleOperation,
+    linera_base_types::{Account, AccountOwner, Amount, ChainId, WithContractAbi},
     views::{RootView, View},
     Contract, ContractRuntime,
 };
 use async_graphql::ComplexObject;
 
-use self::state::{RockPaperScissorsState, PlayerStats};
+use self::state::{RockPaperScissorsState, PlayerStats, RatingChange};
+
+/// Number of rating changes kept per player
+const RATING_HISTORY_LIMIT: usize = 100;
 
 linera_sdk::contract!(RockPaperScissorsContract);
 
@@ -49,7 +59,6 @@ impl Contract for RockPaperScissorsContract {
         self.state.available_rooms.set(Vec::new());
         
         // Initialize leaderboard state
-        self.state.global_leaderboard.set(Vec::new());
         self.state.leaderboard_chain_id.set(parameters.leaderboard_chain_id);
         
         // Check if this chain is the leaderboard chain
@@ -68,7 +77,7 @@ impl Contract for RockPaperScissorsContract {
         eprintln!("[INIT] Configured leaderboard chain: {:?}", parameters.leaderboard_chain_id);
     }
 
-    async fn execute_operation(&mut self, operation: Operation) -> () {
+    async fn execute_operation(&mut self, operation: Operation) -> OperationResponse {
         match operation {
             Operation::SetupLeaderboard { leaderboard_chain_id } => {
                 eprintln!("[SETUP] SetupLeaderboard called on chain {:?} with leaderboard_chain_id: {:?}", 
@@ -76,7 +85,7 @@ impl Contract for RockPaperScissorsContract {
                 
                 // Only allow setup if not already configured
                 if self.state.leaderboard_chain_id.get().is_some() {
-                    panic!("Leaderboard already configured");
+                    return Err(GameError::AlreadyConfigured);
                 }
 
                 // Set the leaderboard chain ID
@@ -89,9 +98,11 @@ impl Contract for RockPaperScissorsContract {
                 } else {
                     eprintln!("[SETUP] This chain is NOT the leaderboard chain");
                 }
+                Ok(OperationOutcome::Done)
             }
             
             Operation::SetPlayerName { name } => {
+                let name = validate_player_name(&name)?;
                 let current_chain = self.runtime.chain_id();
                 eprintln!("[SET_NAME] Setting player name '{}' for chain {:?}", name, current_chain);
                 
@@ -99,128 +110,299 @@ impl Contract for RockPaperScissorsContract {
                 self.state.my_player_name.set(Some(name.clone()));
                 
                 // Send name update to leaderboard chain if this is not the leaderboard chain
-                if let Some(leaderboard_chain_id) = *self.state.leaderboard_chain_id.get() {
-                    if current_chain != leaderboard_chain_id {
-                        let message = GameMessage::UpdatePlayerName {
-                            player_chain: current_chain,
-                            player_name: name,
-                        };
-                        self.runtime.send_message(leaderboard_chain_id, message);
-                    } else {
-                        // If this is the leaderboard chain, update the name mapping directly
-                        let _ = self.state.player_names.insert(&current_chain, name);
-                    }
+                if *self.state.is_leaderboard_chain.get() {
+                    // If this is the leaderboard chain, update the name mapping directly
+                    self.state.player_names.insert(&current_chain, name)
+                        .map_err(|_| GameError::StorageError)?;
+                    Ok(OperationOutcome::Done)
+                } else {
+                    self.send_to_leaderboard(GameMessage::UpdatePlayerName {
+                        player_chain: current_chain,
+                        player_name: name,
+                    })
                 }
             }
             
-            Operation::CreateRoom { room_id, private } => {
+            Operation::CreateRoom { alias, private, allowed_players, invite_code, format, move_timeout_secs, timeout_penalty, entry_fee, currency } => {
                 let current_chain = self.runtime.chain_id();
-                eprintln!("[CREATE_ROOM] CreateRoom called on chain {:?} with room_id: '{}', private: {}", current_chain, room_id, private);
-                
-                // Only allow room creation on leaderboard chain
-                if !*self.state.is_leaderboard_chain.get() {
-                    panic!("Rooms can only be created on the leaderboard chain");
-                }
-                
-                // Check if room with this ID already exists
-                if let Ok(Some(_)) = self.state.rooms.get(&room_id).await {
-                    panic!("Room with ID '{}' already exists", room_id);
-                }
+                eprintln!("[CREATE_ROOM] CreateRoom called on chain {:?} with alias: {:?}, private: {}", current_chain, alias, private);
                 
-                let timestamp = self.runtime.system_time().micros();
-                let room = GameRoom::new(room_id.clone(), timestamp, private);
-                
-                let _ = self.state.rooms.insert(&room_id, room);
+                // The room only stores a hash of the invite code; joiners still send it in clear text
+                let access = RoomAccess {
+                    allowed_players,
+                    invite_code_hash: invite_code.as_deref().map(invite_code_hash),
+                };
                 
-                // Add to available rooms list (only if not private)
-                if !private {
-                    let mut available_rooms = self.state.available_rooms.get().clone();
-                    available_rooms.push(room_id.clone());
-                    self.state.available_rooms.set(available_rooms);
+                // Player chains ask the leaderboard chain to create the room with them as host
+                if !*self.state.is_leaderboard_chain.get() {
+                    let host_name = self.state.my_player_name.get().clone();
+                    let currency = currency.unwrap_or_default();
+                    let stake = self.pay_entry_fee(entry_fee, currency)?;
+                    return self.send_to_leaderboard(GameMessage::CreateRoomRequest {
+                        alias,
+                        host: current_chain,
+                        host_name,
+                        private,
+                        access,
+                        format,
+                        move_timeout_secs,
+                        timeout_penalty,
+                        entry_fee: entry_fee.unwrap_or_default(),
+                        currency,
+                        stake,
+                    });
                 }
                 
-                eprintln!("[CREATE_ROOM] Created room {} on leaderboard chain", room_id);
+                let room = self.create_room(alias, None, private, access, format, move_timeout_secs, timeout_penalty, entry_fee, currency.unwrap_or_default(), None).await?;
+                eprintln!("[CREATE_ROOM] Created room {} on leaderboard chain", room.room_id);
+                Ok(OperationOutcome::Room(room))
             }
             
-            Operation::JoinRoom { room_id } => {
-                let current_chain = self.runtime.chain_id();
-                let leaderboard_chain = self.state.leaderboard_chain_id.get().clone();
+            Operation::CreateSeries { player1, player2, best_of, private, format, move_timeout_secs, timeout_penalty } => {
+                let series = self.create_series(player1, player2, best_of, private, format, move_timeout_secs, timeout_penalty).await?;
+                Ok(OperationOutcome::Series(series))
+            }
+            
+            Operation::CreateTournament { name, format, seeding, max_players, players, private, match_format, move_timeout_secs, timeout_penalty, entry_fee, currency } => {
+                let tournament = self.create_tournament(name, format, seeding, max_players, players, private, match_format, move_timeout_secs, timeout_penalty, entry_fee, currency).await?;
+                Ok(OperationOutcome::Tournament(tournament))
+            }
+            
+            Operation::StartTournament { tournament_id } => {
+                let tournament = self.start_tournament(&tournament_id).await?;
+                Ok(OperationOutcome::Tournament(tournament))
+            }
+            
+            Operation::CancelTournament { tournament_id } => {
+                let tournament = self.cancel_tournament(&tournament_id).await?;
+                Ok(OperationOutcome::Tournament(tournament))
+            }
+            
+            Operation::CreateLeague { name, format, rounds, max_players, players, private, match_format, move_timeout_secs, timeout_penalty } => {
+                let league = self.create_league(name, format, rounds, max_players, players, private, match_format, move_timeout_secs, timeout_penalty).await?;
+                Ok(OperationOutcome::League(league))
+            }
+            
+            Operation::StartLeague { league_id } => {
+                let league = self.start_league(&league_id).await?;
+                Ok(OperationOutcome::League(league))
+            }
+            
+            Operation::RegisterForLeague { league_id } => {
+                let player_chain = self.runtime.chain_id();
+                eprintln!("[LEAGUE] Sending registration for league {}", league_id);
+                self.send_to_leaderboard(GameMessage::RegisterForLeague {
+                    league_id,
+                    player_chain,
+                })
+            }
+            
+            Operation::RegisterForTournament { tournament_id, entry_fee, currency } => {
+                let player_chain = self.runtime.chain_id();
+                eprintln!("[TOURNAMENT] Sending registration for tournament {}", tournament_id);
+                let stake = self.pay_entry_fee(entry_fee, currency.unwrap_or_default())?;
+                self.send_to_leaderboard(GameMessage::RegisterForTournament {
+                    tournament_id,
+                    player_chain,
+                    stake,
+                })
+            }
+            
+            Operation::JoinRoom { room_id, invite_code, entry_fee, currency } => {
+                let player_chain = self.runtime.chain_id();
                 let player_name = self.state.my_player_name.get().clone();
-                
-                match leaderboard_chain {
-                    Some(leader_chain) => {
-                        // Send JoinRoom message to leaderboard chain
-                        let message = GameMessage::JoinRoom {
-                            room_id: room_id.clone(),
-                            player_chain: current_chain,
-                            player_name: player_name.clone(),
-                        };
-                        self.runtime.send_message(leader_chain, message);
-                        eprintln!("[JOIN_ROOM] Sent JoinRoom request to leaderboard chain {:?} with name {:?}", leader_chain, player_name);
-                    }
-                    None => {
-                        eprintln!("[ERROR] No leaderboard chain configured for joining room. Please use SetupLeaderboard operation first");
-                    }
-                }
+                eprintln!("[JOIN_ROOM] Sending JoinRoom request for room {} with name {:?}", room_id, player_name);
+                let stake = self.pay_entry_fee(entry_fee, currency.unwrap_or_default())?;
+                self.send_to_leaderboard(GameMessage::JoinRoom {
+                    room_id,
+                    player_chain,
+                    player_name,
+                    invite_code,
+                    stake,
+                })
             }
             
-            Operation::SubmitChoice { room_id, choice } => {
-                let current_chain = self.runtime.chain_id();
-                let leaderboard_chain = self.state.leaderboard_chain_id.get().clone();
-                
-                match leaderboard_chain {
-                    Some(leader_chain) => {
-                        // Send SubmitChoice message to leaderboard chain
-                        let message = GameMessage::SubmitChoice {
-                            room_id: room_id.clone(),
-                            player_chain: current_chain,
-                            choice,
-                        };
-                        self.runtime.send_message(leader_chain, message);
-                        eprintln!("[SUBMIT_CHOICE] Sent choice {:?} to leaderboard chain {:?}", choice, leader_chain);
-                    }
-                    None => {
-                        eprintln!("[ERROR] No leaderboard chain configured for submitting choice. Please use SetupLeaderboard operation first");
-                    }
-                }
+            Operation::CommitChoice { room_id, commitment } => {
+                let commitment = validate_commitment(&commitment)?;
+                let player_chain = self.runtime.chain_id();
+                eprintln!("[COMMIT_CHOICE] Sending commitment for room {}", room_id);
+                self.send_to_leaderboard(GameMessage::CommitChoice {
+                    room_id,
+                    player_chain,
+                    commitment,
+                })
+            }
+            
+            Operation::RevealChoice { room_id, choice, salt } => {
+                let player_chain = self.runtime.chain_id();
+                eprintln!("[REVEAL_CHOICE] Revealing choice {:?} for room {}", choice, room_id);
+                self.send_to_leaderboard(GameMessage::RevealChoice {
+                    room_id,
+                    player_chain,
+                    choice,
+                    salt,
+                })
+            }
+            
+            Operation::ClaimTimeout { room_id } => {
+                let claimant = self.runtime.chain_id();
+                eprintln!("[CLAIM_TIMEOUT] Sending timeout claim for room {}", room_id);
+                self.send_to_leaderboard(GameMessage::ClaimTimeout {
+                    room_id,
+                    claimant,
+                })
+            }
+            
+            Operation::LeaveRoom { room_id } => {
+                let player_chain = self.runtime.chain_id();
+                eprintln!("[LEAVE_ROOM] Sending request to leave room {}", room_id);
+                self.send_to_leaderboard(GameMessage::LeaveRoom {
+                    room_id,
+                    player_chain,
+                })
+            }
+            
+            Operation::Resign { room_id } => {
+                let player_chain = self.runtime.chain_id();
+                eprintln!("[RESIGN] Resigning from room {}", room_id);
+                self.send_to_leaderboard(GameMessage::Resign {
+                    room_id,
+                    player_chain,
+                })
+            }
+            
+            Operation::OfferRematch { room_id } => {
+                let player_chain = self.runtime.chain_id();
+                eprintln!("[OFFER_REMATCH] Offering a rematch of room {}", room_id);
+                self.send_to_leaderboard(GameMessage::OfferRematch {
+                    room_id,
+                    player_chain,
+                })
+            }
+            
+            Operation::AcceptRematch { room_id } => {
+                let player_chain = self.runtime.chain_id();
+                eprintln!("[ACCEPT_REMATCH] Accepting a rematch of room {}", room_id);
+                self.send_to_leaderboard(GameMessage::AcceptRematch {
+                    room_id,
+                    player_chain,
+                })
+            }
+            
+            Operation::CancelRoom { room_id } => {
+                let requester = self.runtime.chain_id();
+                eprintln!("[CANCEL_ROOM] Sending request to cancel room {}", room_id);
+                self.send_to_leaderboard(GameMessage::CancelRoom {
+                    room_id,
+                    requester,
+                })
+            }
+            
+            Operation::KickPlayer { room_id, player } => {
+                let requester = self.runtime.chain_id();
+                eprintln!("[KICK_PLAYER] Sending request to kick {:?} from room {}", player, room_id);
+                self.send_to_leaderboard(GameMessage::KickPlayer {
+                    room_id,
+                    requester,
+                    player,
+                })
+            }
+            
+            Operation::QuickMatch => {
+                let player_chain = self.runtime.chain_id();
+                let player_name = self.state.my_player_name.get().clone();
+                eprintln!("[QUICK_MATCH] Sending matchmaking request with name {:?}", player_name);
+                let outcome = self.send_to_leaderboard(GameMessage::QueueForMatch {
+                    player_chain,
+                    player_name,
+                })?;
+                self.state.my_queued.set(true);
+                Ok(outcome)
+            }
+            
+            Operation::LeaveQueue => {
+                let player_chain = self.runtime.chain_id();
+                eprintln!("[LEAVE_QUEUE] Sending request to leave the matchmaking queue");
+                let outcome = self.send_to_leaderboard(GameMessage::LeaveQueue { player_chain })?;
+                self.state.my_queued.set(false);
+                Ok(outcome)
             }
             
             Operation::GetAvailableRooms => {
-                // This operation doesn't modify state, just allows querying available rooms
-                // The actual rooms can be queried through the service
+                // Rooms only exist on the leaderboard chain; they can also be queried through the service
+                self.ensure_leaderboard_chain()?;
+                let mut rooms = Vec::new();
+                for room_id in self.state.available_rooms.get().clone() {
+                    rooms.push(self.load_room(&room_id).await?);
+                }
+                Ok(OperationOutcome::Rooms(rooms))
             }
             
-            Operation::GetRoom { room_id: _ } => {
-                // This operation doesn't modify state, just allows querying specific room
-                // The actual room can be queried through the service
+            Operation::GetRoom { room_id } => {
+                // Rooms only exist on the leaderboard chain; they can also be queried through the service
+                self.ensure_leaderboard_chain()?;
+                Ok(OperationOutcome::Room(self.load_room(&room_id).await?))
             }
             
             Operation::GetLeaderboard => {
-                // This operation doesn't modify state, just allows querying leaderboard
-                // The actual leaderboard can be queried through the service
+                // The leaderboard can also be queried through the service
+                self.ensure_leaderboard_chain()?;
+                let leaderboard = self.state.top_players(LeaderboardOrder::Wins, LEADERBOARD_SIZE).await
+                    .map_err(|_| GameError::StorageError)?;
+                Ok(OperationOutcome::Leaderboard(leaderboard))
             }
             
             Operation::GetMyStats => {
                 // This operation doesn't modify state, just allows querying personal stats
                 // The actual stats can be queried through the service
+                Ok(OperationOutcome::Done)
             }
             
             Operation::ResetLeaderboard => {
                 eprintln!("[RESET] ResetLeaderboard called on chain {:?}", self.runtime.chain_id());
                 
                 // Only allow reset on the leaderboard chain
-                if !*self.state.is_leaderboard_chain.get() {
-                    panic!("Reset operation can only be performed on the leaderboard chain");
+                self.ensure_leaderboard_chain()?;
+                
+                // Refund entry fees still held in escrow before dropping the rooms holding them
+                let mut stakes = Vec::new();
+                for room_id in self.state.rooms.indices().await.map_err(|_| GameError::StorageError)? {
+                    if let Some(room) = self.state.rooms.get(&room_id).await.map_err(|_| GameError::StorageError)? {
+                        stakes.extend(room.stakes);
+                    }
+                }
+                for tournament_id in self.state.tournaments.indices().await.map_err(|_| GameError::StorageError)? {
+                    if let Some(tournament) = self.state.tournaments.get(&tournament_id).await.map_err(|_| GameError::StorageError)? {
+                        stakes.extend(tournament.stakes);
+                    }
+                }
+                for stake in &stakes {
+                    self.refund_stake(stake);
                 }
                 
                 // Clear all game data
                 self.state.rooms.clear();
+                self.state.room_aliases.clear();
                 self.state.available_rooms.set(Vec::new());
-                self.state.global_leaderboard.set(Vec::new());
+                self.state.open_rooms.clear();
+                self.state.matchmaking_queue.clear();
+                self.state.leaderboard_entries.clear();
+                self.state.leaderboard_count.set(0);
+                self.state.leaderboard_by_wins.clear();
+                self.state.leaderboard_by_rating.clear();
                 self.state.player_stats.clear();
+                self.state.rating_history.clear();
+                self.state.reward_balances.clear();
+                self.state.series.clear();
+                self.state.tournaments.clear();
+                self.state.leagues.clear();
                 self.state.room_counter.set(0);
+                self.state.series_counter.set(0);
+                self.state.tournament_counter.set(0);
+                self.state.league_counter.set(0);
                 
                 eprintln!("[RESET] Leaderboard and all game data reset completed successfully");
+                Ok(OperationOutcome::Done)
             }
         }
     }
@@ -240,174 +422,198 @@ impl Contract for RockPaperScissorsContract {
         }
 
         match message {
-            GameMessage::JoinRoom { room_id, player_chain, player_name } => {
-                eprintln!("[MESSAGE] Processing JoinRoom: {} from {:?} with name {:?}", room_id, player_chain, player_name);
+            GameMessage::CreateRoomRequest { alias, host, host_name, private, access, format, move_timeout_secs, timeout_penalty, entry_fee, currency, stake } => {
+                eprintln!("[MESSAGE] Processing CreateRoomRequest with alias {:?} from {:?} with name {:?}", alias, host, host_name);
                 
-                // Only process on leaderboard chain
-                if !*self.state.is_leaderboard_chain.get() {
-                    eprintln!("[MESSAGE] This is NOT the leaderboard chain, ignoring JoinRoom message");
-                    return;
+                let result = self.create_room(alias.clone(), Some((host, host_name)), private, access, format, move_timeout_secs, timeout_penalty, Some(entry_fee), currency, stake.clone()).await;
+                if let (Err(_), Some(stake)) = (&result, &stake) {
+                    self.refund_stake(stake);
+                }
+                match result {
+                    Ok(room) => {
+                        eprintln!("[MESSAGE] Created room {} hosted by {:?}", room.room_id, host);
+                        self.runtime.send_message(host, GameMessage::RoomCreated {
+                            room_id: room.room_id,
+                            alias: room.alias,
+                            host,
+                        });
+                    }
+                    Err(error) => self.reject(host, alias, error),
                 }
+            }
+            
+            GameMessage::RoomCreated { room_id, alias, host } => {
+                eprintln!("[MESSAGE] Processing RoomCreated: {} ({:?}) for host {:?}", room_id, alias, host);
                 
-                // Store player name if provided
-                if let Some(ref name) = player_name {
-                    let _ = self.state.player_names.insert(&player_chain, name.clone());
+                if self.runtime.chain_id() == host {
+                    // The host already occupies the first seat
+                    let mut my_rooms = self.state.my_rooms.get().clone();
+                    if !my_rooms.contains(&room_id) {
+                        my_rooms.push(room_id.clone());
+                        self.state.my_rooms.set(my_rooms);
+                    }
+                    self.state.my_current_room.set(Some(room_id));
                 }
+            }
+            
+            GameMessage::JoinRoom { room_id, player_chain, player_name, invite_code, stake } => {
+                eprintln!("[MESSAGE] Processing JoinRoom: {} from {:?} with name {:?}", room_id, player_chain, player_name);
                 
-                let mut success = false;
+                let result = self.join_room(&room_id, player_chain, player_name, invite_code.as_deref(), stake.clone()).await;
+                if let (Err(_), Some(stake)) = (&result, &stake) {
+                    self.refund_stake(stake);
+                }
                 
-                if let Ok(Some(mut room)) = self.state.rooms.get(&room_id).await {
-                    if room.can_join(player_chain) {
-                        if room.add_player(player_chain) {
-                            // Set player name in room
-                            if let Some(ref name) = player_name {
-                                if room.player1 == Some(player_chain) {
-                                    room.player1_name = Some(name.clone());
-                                } else if room.player2 == Some(player_chain) {
-                                    room.player2_name = Some(name.clone());
-                                }
-                            }
-                            
-                            let _ = self.state.rooms.insert(&room_id, room.clone());
-                            success = true;
+                // Send confirmation back to player, with the room code if an alias was used
+                let response_message = match result {
+                    Ok(room_id) => GameMessage::PlayerJoined { room_id, player_chain, error: None },
+                    Err(error) => GameMessage::PlayerJoined { room_id, player_chain, error: Some(error) },
+                };
+                self.runtime.send_message(player_chain, response_message);
+            }
+            
+            GameMessage::PlayerJoined { room_id, player_chain, error } => {
+                eprintln!("[MESSAGE] Processing PlayerJoined: {} for {:?}, error: {:?}", room_id, player_chain, error);
+                
+                let current_chain = self.runtime.chain_id();
+                if current_chain == player_chain {
+                    match error {
+                        None => {
+                            self.state.my_queued.set(false);
                             
-                            // Remove from available rooms if now full
-                            if room.is_full() {
-                                let mut available_rooms = self.state.available_rooms.get().clone();
-                                available_rooms.retain(|id| id != &room_id);
-                                self.state.available_rooms.set(available_rooms);
+                            // Add room to player's room list
+                            let mut my_rooms = self.state.my_rooms.get().clone();
+                            if !my_rooms.contains(&room_id) {
+                                my_rooms.push(room_id.clone());
+                                self.state.my_rooms.set(my_rooms);
                             }
                             
-                            eprintln!("[MESSAGE] Player {:?} ({:?}) joined room {} successfully", player_chain, player_name, room_id);
+                            // Set as current room
+                            self.state.my_current_room.set(Some(room_id.clone()));
                         }
-                    } else {
-                        eprintln!("[MESSAGE] Player {:?} cannot join room {} - room full or already joined", player_chain, room_id);
+                        Some(error) => self.record_rejection(Some(room_id), error),
                     }
-                } else {
-                    eprintln!("[MESSAGE] Room {} not found for join request", room_id);
                 }
+            }
+            
+            GameMessage::ActionRejected { room_id, error } => {
+                eprintln!("[MESSAGE] Processing ActionRejected for room {:?}: {}", room_id, error);
                 
-                // Send confirmation back to player
-                let response_message = GameMessage::PlayerJoined {
-                    room_id: room_id.clone(),
-                    player_chain,
-                    success,
-                };
-                self.runtime.send_message(player_chain, response_message);
+                // A rejected queue request means we are not waiting for a match
+                if room_id.is_none() && error != GameError::AlreadyQueued {
+                    self.state.my_queued.set(false);
+                }
+                self.record_rejection(room_id, error);
+            }
+            
+            GameMessage::CommitChoice { room_id, player_chain, commitment } => {
+                eprintln!("[MESSAGE] Processing CommitChoice: {} from {:?}", room_id, player_chain);
+                
+                if let Err(error) = self.commit_choice(&room_id, player_chain, commitment).await {
+                    self.reject(player_chain, Some(room_id), error);
+                }
+            }
+            
+            GameMessage::RevealPhaseStarted { room_id, round_number } => {
+                eprintln!("[MESSAGE] Both players committed in room {} round {}, choices can be revealed", room_id, round_number);
+                
+                // Revealing is triggered by the player's client through RevealChoice
+            }
+            
+            GameMessage::RevealChoice { room_id, player_chain, choice, salt } => {
+                eprintln!("[MESSAGE] Processing RevealChoice: {} from {:?} with choice {:?}", room_id, player_chain, choice);
+                
+                if let Err(error) = self.reveal_choice(&room_id, player_chain, choice, &salt).await {
+                    self.reject(player_chain, Some(room_id), error);
+                }
+            }
+            
+            GameMessage::ClaimTimeout { room_id, claimant } => {
+                eprintln!("[MESSAGE] Processing ClaimTimeout: {} from {:?}", room_id, claimant);
+                
+                if let Err(error) = self.claim_timeout(&room_id).await {
+                    self.reject(claimant, Some(room_id), error);
+                }
+            }
+            
+            GameMessage::RoomClosed { room_id, status } => {
+                eprintln!("[MESSAGE] Processing RoomClosed for room {} with status {:?}", room_id, status);
+                
+                self.forget_room(&room_id);
+            }
+            
+            GameMessage::LeaveRoom { room_id, player_chain } => {
+                eprintln!("[MESSAGE] Processing LeaveRoom: {} from {:?}", room_id, player_chain);
+                
+                if let Err(error) = self.leave_room(&room_id, player_chain).await {
+                    self.reject(player_chain, Some(room_id), error);
+                }
+            }
+            
+            GameMessage::Resign { room_id, player_chain } => {
+                eprintln!("[MESSAGE] Processing Resign: {} from {:?}", room_id, player_chain);
+                
+                if let Err(error) = self.resign(&room_id, player_chain).await {
+                    self.reject(player_chain, Some(room_id), error);
+                }
+            }
+            
+            GameMessage::OfferRematch { room_id, player_chain } => {
+                eprintln!("[MESSAGE] Processing OfferRematch: {} from {:?}", room_id, player_chain);
+                
+                if let Err(error) = self.offer_rematch(&room_id, player_chain).await {
+                    self.reject(player_chain, Some(room_id), error);
+                }
+            }
+            
+            GameMessage::AcceptRematch { room_id, player_chain } => {
+                eprintln!("[MESSAGE] Processing AcceptRematch: {} from {:?}", room_id, player_chain);
+                
+                if let Err(error) = self.accept_rematch(&room_id, player_chain).await {
+                    self.reject(player_chain, Some(room_id), error);
+                }
+            }
+            
+            GameMessage::RematchOffered { room_id, offered_by } => {
+                eprintln!("[MESSAGE] {:?} offered a rematch of room {}", offered_by, room_id);
+                
+                // Accepting is triggered by the player's client through AcceptRematch
             }
             
-            GameMessage::PlayerJoined { room_id, player_chain, success } => {
-                eprintln!("[MESSAGE] Processing PlayerJoined: {} for {:?}, success: {}", room_id, player_chain, success);
+            GameMessage::SeriesFinished { series_id, winner, player1_wins, player2_wins } => {
+                eprintln!("[MESSAGE] Series {} finished {}-{}, winner: {:?}", series_id, player1_wins, player2_wins, winner);
                 
                 let current_chain = self.runtime.chain_id();
-                if current_chain == player_chain {
-                    if success {
-                        // Add room to player's room list
-                        let mut my_rooms = self.state.my_rooms.get().clone();
-                        if !my_rooms.contains(&room_id) {
-                            my_rooms.push(room_id.clone());
-                            self.state.my_rooms.set(my_rooms);
-                        }
-                        
-                        // Set as current room
-                        self.state.my_current_room.set(Some(room_id.clone()));
-                        
-                        eprintln!("[MESSAGE] Successfully joined room {}", room_id);
-                    } else {
-                        eprintln!("[MESSAGE] Failed to join room {}", room_id);
-                    }
+                let mut my_stats = self.state.my_stats.get().clone().unwrap_or_else(|| PlayerStats::new(current_chain));
+                my_stats.series_played += 1;
+                if winner == current_chain {
+                    my_stats.series_won += 1;
                 }
+                self.state.my_stats.set(Some(my_stats));
             }
             
-            GameMessage::SubmitChoice { room_id, player_chain, choice } => {
-                eprintln!("[MESSAGE] Processing SubmitChoice: {} from {:?} with choice {:?}", room_id, player_chain, choice);
+            GameMessage::CancelRoom { room_id, requester } => {
+                eprintln!("[MESSAGE] Processing CancelRoom: {} from {:?}", room_id, requester);
                 
-                // Only process on leaderboard chain
-                if !*self.state.is_leaderboard_chain.get() {
-                    eprintln!("[MESSAGE] This is NOT the leaderboard chain, ignoring SubmitChoice message");
-                    return;
+                if let Err(error) = self.cancel_room(&room_id, requester).await {
+                    self.reject(requester, Some(room_id), error);
                 }
+            }
+            
+            GameMessage::KickPlayer { room_id, requester, player } => {
+                eprintln!("[MESSAGE] Processing KickPlayer: {:?} from {} by {:?}", player, room_id, requester);
                 
-                if let Ok(Some(mut room)) = self.state.rooms.get(&room_id).await {
-                    if room.set_choice(player_chain, choice) {
-                        eprintln!("[MESSAGE] Choice {:?} set for player {:?} in room {}", choice, player_chain, room_id);
-                        
-                        // Check if both players have chosen
-                        if room.both_players_chose() {
-                            eprintln!("[MESSAGE] Both players have chosen, calculating round result");
-                            
-                            if let Some((round_result, round_winner)) = room.calculate_round_result() {
-                                let player1_choice = room.player1_choice.unwrap_or(Choice::Rock); // Should not happen
-                                let player2_choice = room.player2_choice.unwrap_or(Choice::Rock); // Should not happen
-                                
-                                // Send round completed message to both players
-                                let round_message = GameMessage::RoundCompleted {
-                                    room_id: room_id.clone(),
-                                    player1_choice,
-                                    player2_choice,
-                                    round_winner,
-                                    round_result,
-                                    game_result: room.game_result.clone(),
-                                };
-                                
-                                if let Some(player1) = room.player1 {
-                                    self.runtime.send_message(player1, round_message.clone());
-                                }
-                                if let Some(player2) = room.player2 {
-                                    self.runtime.send_message(player2, round_message.clone());
-                                }
-                                
-                                // Check if game is finished
-                                if room.game_result.is_finished {
-                                    if let Some(winner) = room.game_result.winner {
-                                        eprintln!("[MESSAGE] Game finished! Winner: {:?}", winner);
-                                        
-                                        // Send game finished message
-                                        let game_finished_message = GameMessage::GameFinished {
-                                            room_id: room_id.clone(),
-                                            winner,
-                                            final_result: room.game_result.clone(),
-                                        };
-                                        
-                                        if let Some(player1) = room.player1 {
-                                            self.runtime.send_message(player1, game_finished_message.clone());
-                                        }
-                                        if let Some(player2) = room.player2 {
-                                            self.runtime.send_message(player2, game_finished_message.clone());
-                                        }
-                                        
-                                        // Update leaderboard stats only for non-private rooms
-                                        if !room.private {
-                                            if let Some(player1) = room.player1 {
-                                                let won = winner == player1;
-                                                self.update_leaderboard_stats(player1, won).await;
-                                            }
-                                            if let Some(player2) = room.player2 {
-                                                let won = winner == player2;
-                                                self.update_leaderboard_stats(player2, won).await;
-                                            }
-                                        } else {
-                                            eprintln!(
-                                                "[LEADERBOARD] Skipping leaderboard update for private room {}", 
-                                                room_id
-                                            );
-                                        }
-                                        
-                                        // Remove room from available rooms and clean up
-                                        let mut available_rooms = self.state.available_rooms.get().clone();
-                                        available_rooms.retain(|id| id != &room_id);
-                                        self.state.available_rooms.set(available_rooms);
-                                    }
-                                }
-                            }
-                        }
-                        
-                        let _ = self.state.rooms.insert(&room_id, room);
-                    } else {
-                        eprintln!("[MESSAGE] Failed to set choice for player {:?} in room {}", player_chain, room_id);
-                    }
-                } else {
-                    eprintln!("[MESSAGE] Room {} not found for choice submission", room_id);
+                if let Err(error) = self.kick_player(&room_id, requester, player).await {
+                    self.reject(requester, Some(room_id), error);
+                }
+            }
+            
+            GameMessage::SeatVacated { room_id, player_chain, kicked } => {
+                eprintln!("[MESSAGE] Processing SeatVacated for room {}: {:?} {}", 
+                    room_id, player_chain, if kicked { "was kicked" } else { "left" });
+                
+                if self.runtime.chain_id() == player_chain {
+                    self.forget_room(&room_id);
                 }
             }
             
@@ -436,17 +642,12 @@ impl Contract for RockPaperScissorsContract {
                 
                 let mut my_stats = self.state.my_stats.get().clone().unwrap_or_else(|| PlayerStats::new(current_chain));
                 my_stats.add_game(won, timestamp);
+                if final_result.resigned_by == Some(current_chain) {
+                    my_stats.resignations += 1;
+                }
                 self.state.my_stats.set(Some(my_stats));
                 
-                // Remove room from my rooms
-                let mut my_rooms = self.state.my_rooms.get().clone();
-                my_rooms.retain(|id| id != &room_id);
-                self.state.my_rooms.set(my_rooms);
-                
-                // Clear current room if it was this room
-                if self.state.my_current_room.get().as_ref() == Some(&room_id) {
-                    self.state.my_current_room.set(None);
-                }
+                self.forget_room(&room_id);
                 
                 eprintln!("[MESSAGE] Updated personal stats for game completion");
             }
@@ -454,9 +655,8 @@ impl Contract for RockPaperScissorsContract {
             GameMessage::UpdateLeaderboard { player_chain, won } => {
                 eprintln!("[MESSAGE] Processing UpdateLeaderboard for {:?}, won: {}", player_chain, won);
                 
-                // Only process on leaderboard chain
-                if !*self.state.is_leaderboard_chain.get() {
-                    eprintln!("[MESSAGE] This is NOT the leaderboard chain, ignoring UpdateLeaderboard message");
+                if let Err(error) = self.ensure_leaderboard_chain() {
+                    self.reject(player_chain, None, error);
                     return;
                 }
                 
@@ -466,15 +666,63 @@ impl Contract for RockPaperScissorsContract {
             GameMessage::UpdatePlayerName { player_chain, player_name } => {
                 eprintln!("[MESSAGE] Processing UpdatePlayerName for {:?}: '{}'", player_chain, player_name);
                 
-                // Only process on leaderboard chain
-                if !*self.state.is_leaderboard_chain.get() {
-                    eprintln!("[MESSAGE] This is NOT the leaderboard chain, ignoring UpdatePlayerName message");
-                    return;
+                let result = self.ensure_leaderboard_chain()
+                    .and_then(|()| validate_player_name(&player_name))
+                    .and_then(|name| {
+                        // Store the player name mapping
+                        self.state.player_names.insert(&player_chain, name)
+                            .map_err(|_| GameError::StorageError)
+                    });
+                if let Err(error) = result {
+                    self.reject(player_chain, None, error);
+                }
+            }
+            
+            GameMessage::RegisterForTournament { tournament_id, player_chain, stake } => {
+                eprintln!("[MESSAGE] Processing RegisterForTournament: {} from {:?}", tournament_id, player_chain);
+                
+                if let Err(error) = self.register_for_tournament(&tournament_id, player_chain, stake.clone()).await {
+                    if let Some(stake) = stake {
+                        self.refund_stake(&stake);
+                    }
+                    self.reject(player_chain, Some(tournament_id), error);
+                }
+            }
+            
+            GameMessage::TournamentFinished { tournament_id, champion } => {
+                eprintln!("[MESSAGE] Tournament {} finished, champion: {:?}", tournament_id, champion);
+                
+                // This is mainly for UI updates on player chains
+            }
+            
+            GameMessage::RegisterForLeague { league_id, player_chain } => {
+                eprintln!("[MESSAGE] Processing RegisterForLeague: {} from {:?}", league_id, player_chain);
+                
+                if let Err(error) = self.register_for_league(&league_id, player_chain).await {
+                    self.reject(player_chain, Some(league_id), error);
+                }
+            }
+            
+            GameMessage::LeagueFinished { league_id, winner } => {
+                eprintln!("[MESSAGE] League {} finished, winner: {:?}", league_id, winner);
+                
+                // This is mainly for UI updates on player chains
+            }
+            
+            GameMessage::QueueForMatch { player_chain, player_name } => {
+                eprintln!("[MESSAGE] Processing QueueForMatch from {:?} with name {:?}", player_chain, player_name);
+                
+                if let Err(error) = self.enqueue_for_match(player_chain, player_name).await {
+                    self.reject(player_chain, None, error);
                 }
+            }
+            
+            GameMessage::LeaveQueue { player_chain } => {
+                eprintln!("[MESSAGE] Processing LeaveQueue from {:?}", player_chain);
                 
-                // Store the player name mapping
-                let _ = self.state.player_names.insert(&player_chain, player_name);
-                eprintln!("[MESSAGE] Updated player name for chain {:?}", player_chain);
+                if let Err(error) = self.leave_queue(player_chain).await {
+                    self.reject(player_chain, None, error);
+                }
             }
         }
     }
@@ -485,93 +733,1412 @@ impl Contract for RockPaperScissorsContract {
 }
 
 impl RockPaperScissorsContract {
-    async fn update_leaderboard_stats(&mut self, player_chain: ChainId, won: bool) {
-        eprintln!("[LEADERBOARD] Updating stats for {:?}, won: {}", player_chain, won);
-        
+    fn ensure_leaderboard_chain(&self) -> Result<(), GameError> {
+        if *self.state.is_leaderboard_chain.get() {
+            Ok(())
+        } else {
+            Err(GameError::NotLeaderboardChain)
+        }
+    }
+    
+    /// Forward a request to the leaderboard chain
+    fn send_to_leaderboard(&mut self, message: GameMessage) -> OperationResponse {
+        let leaderboard_chain = self.state.leaderboard_chain_id.get().ok_or(GameError::LeaderboardNotConfigured)?;
+        self.runtime.send_message(leaderboard_chain, message);
+        Ok(OperationOutcome::SentToLeaderboard)
+    }
+    
+    /// Report a rejected request back to the chain that sent it
+    fn reject(&mut self, player_chain: ChainId, room_id: Option<String>, error: GameError) {
+        eprintln!("[MESSAGE] Rejecting request from {:?} for room {:?}: {}", player_chain, room_id, error);
+        self.runtime.send_message(player_chain, GameMessage::ActionRejected { room_id, error });
+    }
+    
+    /// Remember the last rejected request on the player chain so clients can display it
+    fn record_rejection(&mut self, room_id: Option<String>, error: GameError) {
         let timestamp = self.runtime.system_time().micros();
+        self.state.my_last_error.set(Some(RejectedAction { room_id, error, timestamp }));
+    }
+    
+    /// Drop a room this player no longer sits in from the player chain's room lists
+    fn forget_room(&mut self, room_id: &str) {
+        // Remove room from my rooms
+        let mut my_rooms = self.state.my_rooms.get().clone();
+        my_rooms.retain(|id| id != room_id);
+        self.state.my_rooms.set(my_rooms);
         
-        // Get or create player stats
-        let mut stats = match self.state.player_stats.get(&player_chain).await {
-            Ok(Some(existing_stats)) => existing_stats,
-            _ => PlayerStats::new(player_chain),
+        // Clear current room if it was this room
+        if self.state.my_current_room.get().as_deref() == Some(room_id) {
+            self.state.my_current_room.set(None);
+        }
+    }
+    
+    /// Send a message to every seated player
+    fn send_to_players(&mut self, room: &GameRoom, message: GameMessage) {
+        for player_chain in [room.player1, room.player2].into_iter().flatten() {
+            self.runtime.send_message(player_chain, message.clone());
+        }
+    }
+    
+    /// Fail unless `requester` hosts the room; rooms created on the leaderboard chain are
+    /// hosted by the leaderboard chain itself
+    fn check_host(&mut self, room: &GameRoom, requester: ChainId) -> Result<(), GameError> {
+        let host = match room.host {
+            Some(host) => host,
+            None => self.runtime.chain_id(),
         };
-        
-        // Update stats
-        stats.add_game(won, timestamp);
-        
-        // Save updated stats
-        let _ = self.state.player_stats.insert(&player_chain, stats.clone());
-        
-        // Rebuild global leaderboard
-        self.rebuild_global_leaderboard().await;
-        
-        eprintln!("[LEADERBOARD] Updated stats for {:?}: games={}, wins={}, losses={}, win_rate={:.2}%", 
-            player_chain, stats.games_played, stats.games_won, stats.games_lost, stats.win_rate());
+        if requester == host {
+            Ok(())
+        } else {
+            Err(GameError::NotHost)
+        }
     }
     
-    /// Rebuild the global leaderboard from all player stats
-    async fn rebuild_global_leaderboard(&mut self) {
-        // Collect all player stats
-        let mut all_entries = Vec::new();
-
-        // Get all player chain IDs who have stats
-        match self.state.player_stats.indices().await {
-            Ok(player_chains) => {
-                eprintln!("[LEADERBOARD] Found {} players with stats", player_chains.len());
-
-                for player_chain in player_chains {
-                    if let Ok(Some(stats)) = self.state.player_stats.get(&player_chain).await {
-                        // Get player name if available
-                        let player_name = match self.state.player_names.get(&player_chain).await {
-                            Ok(Some(name)) => Some(name),
-                            _ => None,
-                        };
-                        
-                        let entry = LeaderboardEntry {
-                            chain_id: stats.chain_id,
-                            wins: stats.games_won,
-                            losses: stats.games_lost,
-                            total_games: stats.games_played,
-                            player_name: player_name.clone(),
-                        };
-                        all_entries.push(entry);
-                        eprintln!("[LEADERBOARD] Added {:?} ({:?}) with {} wins to rebuild list", player_chain, player_name, stats.games_won);
-                    }
-                }
-            }
-            Err(_) => {
-                eprintln!("[LEADERBOARD] Failed to get player chains, returning");
-                return;
+    /// Account of this application, holding entry fees in escrow on the leaderboard chain
+    fn escrow_owner(&mut self) -> AccountOwner {
+        self.runtime.application_id().forget_abi().into()
+    }
+    
+    /// Move `amount` of `currency` from `source` to `destination`: native tokens with a runtime
+    /// transfer, fungible tokens with a call to the configured token application
+    fn transfer_funds(&mut self, currency: Currency, source: AccountOwner, destination: Account, amount: Amount) -> Result<(), GameError> {
+        match currency {
+            Currency::Native => self.runtime.transfer(source, destination, amount),
+            Currency::Token => {
+                let token = self.runtime.application_parameters().fungible_token
+                    .ok_or(GameError::TokenNotConfigured)?;
+                let transfer = FungibleOperation::Transfer {
+                    owner: source,
+                    amount,
+                    target_account: destination,
+                };
+                self.runtime.call_application(true, token, &transfer);
             }
         }
-
-        // Sort by wins descending, then by win rate, then by total games
-        all_entries.sort_by(|a, b| {
-            b.wins.cmp(&a.wins)
-                .then_with(|| b.win_rate().partial_cmp(&a.win_rate()).unwrap_or(std::cmp::Ordering::Equal))
-                .then_with(|| b.total_games.cmp(&a.total_games))
-        });
-        eprintln!("[LEADERBOARD] Sorted {} entries", all_entries.len());
-
-        // Take top 100
-        let top_100: Vec<LeaderboardEntry> = all_entries.into_iter().take(100).collect();
-        eprintln!("[LEADERBOARD] Taking top {} entries for leaderboard", top_100.len());
-
-        // Update the global leaderboard
-        self.state.global_leaderboard.set(top_100.clone());
-        eprintln!("[LEADERBOARD] Global leaderboard updated with {} entries", top_100.len());
+        Ok(())
+    }
+    
+    /// Check that fees can be paid in `currency`
+    fn ensure_currency(&mut self, currency: Currency) -> Result<(), GameError> {
+        if currency == Currency::Token && self.runtime.application_parameters().fungible_token.is_none() {
+            return Err(GameError::TokenNotConfigured);
+        }
+        Ok(())
+    }
+    
+    /// Transfer an entry fee from the signer of the current block to the escrow account on
+    /// the leaderboard chain. The leaderboard chain refunds it if the request is rejected.
+    fn pay_entry_fee(&mut self, entry_fee: Option<Amount>, currency: Currency) -> Result<Option<Stake>, GameError> {
+        let Some(amount) = entry_fee.filter(|amount| !amount.is_zero()) else {
+            return Ok(None);
+        };
+        let leaderboard_chain = self.state.leaderboard_chain_id.get().ok_or(GameError::LeaderboardNotConfigured)?;
+        let owner = self.runtime.authenticated_signer().ok_or(GameError::EntryFeeRequiresSigner)?;
+        let escrow = Account::new(leaderboard_chain, self.escrow_owner());
+        self.transfer_funds(currency, owner, escrow, amount)?;
+        eprintln!("[ESCROW] Paid entry fee of {} ({:?}) to the leaderboard chain", amount, currency);
         
-        // Log final leaderboard state
-        eprintln!("[LEADERBOARD] Final leaderboard state:");
-        for (i, entry) in top_100.iter().take(10).enumerate() {
-            let display_name = entry.player_name.as_ref().map(|s| s.as_str()).unwrap_or("Anonymous");
-            eprintln!("[LEADERBOARD] #{}: {} ({:?}) - {} wins, {:.2}% win rate ({} total games)", 
-                i + 1, display_name, entry.chain_id, entry.wins, entry.win_rate(), entry.total_games);
+        Ok(Some(Stake {
+            player: self.runtime.chain_id(),
+            owner,
+            amount,
+            currency,
+        }))
+    }
+    
+    /// Return an entry fee from escrow to the account it was paid from
+    fn refund_stake(&mut self, stake: &Stake) {
+        eprintln!("[ESCROW] Refunding {} ({:?}) to {:?}", stake.amount, stake.currency, stake.player);
+        let source = self.escrow_owner();
+        let destination = Account::new(stake.player, stake.owner);
+        if let Err(error) = self.transfer_funds(stake.currency, source, destination, stake.amount) {
+            eprintln!("[ESCROW] Refund failed: {:?}", error);
+        }
+    }
+    
+    /// Pay all escrowed `stakes` to `winner`, on the account they paid their own entry fee
+    /// from, or to their chain if they did not pay one. The stakes of a room or tournament
+    /// all share its currency.
+    fn pay_prize(&mut self, winner: ChainId, stakes: Vec<Stake>) {
+        let Some(currency) = stakes.first().map(|stake| stake.currency) else {
+            return;
+        };
+        let prize = stakes.iter().fold(Amount::ZERO, |prize, stake| prize.saturating_add(stake.amount));
+        let owner = stakes.iter()
+            .find(|stake| stake.player == winner)
+            .map_or(AccountOwner::CHAIN, |stake| stake.owner);
+        eprintln!("[ESCROW] Paying prize of {} ({:?}) to {:?}", prize, currency, winner);
+        let source = self.escrow_owner();
+        if let Err(error) = self.transfer_funds(currency, source, Account::new(winner, owner), prize) {
+            eprintln!("[ESCROW] Prize payout failed: {:?}", error);
+        }
+    }
+    
+    /// Load a room by its code, a loosely typed code (see `normalize_room_code`) or its alias
+    async fn load_room(&self, room_id: &str) -> Result<GameRoom, GameError> {
+        if let Some(room) = self.state.rooms.get(room_id).await.map_err(|_| GameError::StorageError)? {
+            return Ok(room);
         }
         
-        eprintln!("[LEADERBOARD] Rebuild completed successfully");
+        let code = match normalize_room_code(room_id) {
+            Some(code) if self.state.rooms.contains_key(&code).await.map_err(|_| GameError::StorageError)? => code,
+            _ => self.state.room_aliases.get(room_id).await
+                .map_err(|_| GameError::StorageError)?
+                .ok_or(GameError::RoomNotFound)?,
+        };
+        self.state.rooms.get(&code).await
+            .map_err(|_| GameError::StorageError)?
+            .ok_or(GameError::RoomNotFound)
     }
-}
+    
+    /// Allocate the next room code from `room_counter`
+    async fn allocate_room_id(&mut self) -> Result<String, GameError> {
+        let mut counter = *self.state.room_counter.get();
+        let room_id = loop {
+            counter += 1;
+            let room_id = room_code(counter);
+            if !self.state.rooms.contains_key(&room_id).await.map_err(|_| GameError::StorageError)? {
+                break room_id;
+            }
+        };
+        self.state.room_counter.set(counter);
+        Ok(room_id)
+    }
+    
+    /// Create a room on the leaderboard chain. A hosting player chain takes the first seat.
+    #[allow(clippy::too_many_arguments)]
+    async fn create_room(
+        &mut self,
+        alias: Option<String>,
+        host: Option<(ChainId, Option<String>)>,
+        private: bool,
+        access: RoomAccess,
+        format: Option<MatchFormat>,
+        move_timeout_secs: Option<u64>,
+        timeout_penalty: Option<TimeoutPenalty>,
+        entry_fee: Option<Amount>,
+        currency: Currency,
+        host_stake: Option<Stake>,
+    ) -> Result<GameRoom, GameError> {
+        self.ensure_leaderboard_chain()?;
+        self.ensure_currency(currency)?;
+        
+        let format = format.unwrap_or_default();
+        format.validate()?;
+        
+        // Aliases share the lookup namespace with room codes, so they must not clash with either
+        let alias = alias.map(|alias| validate_room_alias(&alias)).transpose()?;
+        if let Some(ref alias) = alias {
+            let taken = self.state.room_aliases.contains_key(alias).await.map_err(|_| GameError::StorageError)?
+                || normalize_room_code(alias).is_some()
+                || self.state.rooms.contains_key(alias).await.map_err(|_| GameError::StorageError)?;
+            if taken {
+                return Err(GameError::RoomAlreadyExists);
+            }
+        }
+        
+        let timestamp = self.runtime.system_time().micros();
+        let mut room = GameRoom::new(
+            String::new(), // Allocated once the room is known to be valid
+            timestamp,
+            private,
+            format,
+            move_timeout_secs.unwrap_or(DEFAULT_MOVE_TIMEOUT_SECS),
+            timeout_penalty.unwrap_or_default(),
+        );
+        room.entry_fee = entry_fee.unwrap_or_default();
+        room.currency = currency;
+        
+        if let Some((host, host_name)) = host {
+            // Store host name if provided
+            let host_name = host_name.and_then(|name| validate_player_name(&name).ok());
+            if let Some(ref name) = host_name {
+                let _ = self.state.player_names.insert(&host, name.clone());
+            }
+            
+            room.host = Some(host);
+            room.check_entry_fee(host_stake.as_ref())?;
+            room.add_player(host, None)?;
+            room.player1_name = host_name;
+            if let Some(stake) = host_stake {
+                room.escrow(stake);
+            }
+        }
+        room.set_access(access)?;
+        
+        // A rejected room must not use up a room code
+        room.room_id = self.allocate_room_id().await?;
+        if let Some(alias) = alias {
+            self.state.room_aliases.insert(&alias, room.room_id.clone())
+                .map_err(|_| GameError::StorageError)?;
+            room.alias = Some(alias);
+        }
+        
+        self.save_room(&room).await;
+        Ok(room)
+    }
+    
+    async fn join_room(
+        &mut self,
+        room_id: &str,
+        player_chain: ChainId,
+        player_name: Option<String>,
+        invite_code: Option<&str>,
+        stake: Option<Stake>,
+    ) -> Result<String, GameError> {
+        self.ensure_leaderboard_chain()?;
+        
+        // Store player name if provided
+        let player_name = player_name.and_then(|name| validate_player_name(&name).ok());
+        if let Some(ref name) = player_name {
+            let _ = self.state.player_names.insert(&player_chain, name.clone());
+        }
+        
+        let mut room = self.load_room(room_id).await?;
+        room.check_entry_fee(stake.as_ref())?;
+        room.add_player(player_chain, invite_code)?;
+        if let Some(stake) = stake {
+            room.escrow(stake);
+        }
+        
+        // Set player name in room
+        if room.player1 == Some(player_chain) {
+            room.player1_name = player_name.clone();
+        } else {
+            room.player2_name = player_name.clone();
+        }
+        
+        // Start the first round clock once both seats are taken
+        if room.is_full() {
+            let now = self.runtime.system_time().micros();
+            room.reset_deadline(now);
+        }
+        
+        self.save_room(&room).await;
+        eprintln!("[MESSAGE] Player {:?} ({:?}) joined room {} successfully", player_chain, player_name, room.room_id);
+        Ok(room.room_id)
+    }
+    
+    async fn leave_room(&mut self, room_id: &str, player_chain: ChainId) -> Result<(), GameError> {
+        self.ensure_leaderboard_chain()?;
+        
+        let mut room = self.load_room(room_id).await?;
+        
+        // A room cannot outlive its host
+        if room.host == Some(player_chain) {
+            return self.close_cancelled_room(room).await;
+        }
+        
+        room.remove_player(player_chain)?;
+        if let Some(stake) = room.release_stake(player_chain) {
+            self.refund_stake(&stake);
+        }
+        eprintln!("[MESSAGE] Player {:?} left room {}", player_chain, room.room_id);
+        self.announce_vacated_seat(&room, player_chain, false);
+        self.save_room(&room).await;
+        Ok(())
+    }
+    
+    async fn resign(&mut self, room_id: &str, player_chain: ChainId) -> Result<(), GameError> {
+        self.ensure_leaderboard_chain()?;
+        
+        let mut room = self.load_room(room_id).await?;
+        let room_id = room.room_id.clone();
+        let winner = room.resign(player_chain)?;
+        eprintln!("[MESSAGE] Player {:?} resigned room {}, {:?} wins", player_chain, room_id, winner);
+        
+        self.settle_game(&room_id, &mut room).await;
+        self.save_room(&room).await;
+        Ok(())
+    }
+    
+    async fn offer_rematch(&mut self, room_id: &str, player_chain: ChainId) -> Result<(), GameError> {
+        self.ensure_leaderboard_chain()?;
+        
+        let mut room = self.load_room(room_id).await?;
+        
+        // Offering back to an opponent who already offered accepts their offer
+        if room.check_rematch_accept(player_chain).is_ok() {
+            return self.start_rematch(room).await;
+        }
+        
+        room.offer_rematch(player_chain)?;
+        eprintln!("[MESSAGE] Player {:?} offered a rematch of room {}", player_chain, room.room_id);
+        
+        let opponent = if room.player1 == Some(player_chain) { room.player2 } else { room.player1 };
+        if let Some(opponent) = opponent {
+            self.runtime.send_message(opponent, GameMessage::RematchOffered {
+                room_id: room.room_id.clone(),
+                offered_by: player_chain,
+            });
+        }
+        self.save_room(&room).await;
+        Ok(())
+    }
+    
+    async fn accept_rematch(&mut self, room_id: &str, player_chain: ChainId) -> Result<(), GameError> {
+        self.ensure_leaderboard_chain()?;
+        
+        let room = self.load_room(room_id).await?;
+        room.check_rematch_accept(player_chain)?;
+        self.start_rematch(room).await
+    }
+    
+    /// Create the rematch room, link it to the finished one and seat both players
+    async fn start_rematch(&mut self, mut room: GameRoom) -> Result<(), GameError> {
+        let rematch_id = self.allocate_room_id().await?;
+        let now = self.runtime.system_time().micros();
+        let mut rematch = room.rematch(rematch_id.clone(), now)?;
+        rematch.reset_deadline(now);
+        
+        room.next_room = Some(rematch_id.clone());
+        room.rematch_offered_by = None;
+        self.save_room(&room).await;
+        self.save_room(&rematch).await;
+        eprintln!("[MESSAGE] Rematch of room {} created as {}", room.room_id, rematch_id);
+        
+        for player_chain in [rematch.player1, rematch.player2].into_iter().flatten() {
+            self.runtime.send_message(player_chain, GameMessage::PlayerJoined {
+                room_id: rematch_id.clone(),
+                player_chain,
+                error: None,
+            });
+        }
+        Ok(())
+    }
+    
+    async fn cancel_room(&mut self, room_id: &str, requester: ChainId) -> Result<(), GameError> {
+        self.ensure_leaderboard_chain()?;
+        
+        let room = self.load_room(room_id).await?;
+        self.check_host(&room, requester)?;
+        self.close_cancelled_room(room).await
+    }
+    
+    async fn kick_player(&mut self, room_id: &str, requester: ChainId, player: ChainId) -> Result<(), GameError> {
+        self.ensure_leaderboard_chain()?;
+        
+        let mut room = self.load_room(room_id).await?;
+        self.check_host(&room, requester)?;
+        if room.host == Some(player) {
+            return Err(GameError::CannotKickHost);
+        }
+        
+        room.remove_player(player)?;
+        if let Some(stake) = room.release_stake(player) {
+            self.refund_stake(&stake);
+        }
+        // A kicked player cannot come back through the allow-list
+        room.allowed_players.retain(|allowed| *allowed != player);
+        eprintln!("[MESSAGE] Player {:?} was kicked from room {}", player, room.room_id);
+        self.announce_vacated_seat(&room, player, true);
+        self.save_room(&room).await;
+        Ok(())
+    }
+    
+    /// Cancel a room and tell everyone seated that it is closed
+    async fn close_cancelled_room(&mut self, mut room: GameRoom) -> Result<(), GameError> {
+        room.cancel()?;
+        eprintln!("[MESSAGE] Room {} cancelled", room.room_id);
+        for stake in room.take_stakes() {
+            self.refund_stake(&stake);
+        }
+        
+        let closed_message = GameMessage::RoomClosed {
+            room_id: room.room_id.clone(),
+            status: room.status,
+        };
+        self.send_to_players(&room, closed_message);
+        self.save_room(&room).await;
+        Ok(())
+    }
+    
+    /// Tell the removed player and whoever is still seated that a seat was freed
+    fn announce_vacated_seat(&mut self, room: &GameRoom, player_chain: ChainId, kicked: bool) {
+        let message = GameMessage::SeatVacated {
+            room_id: room.room_id.clone(),
+            player_chain,
+            kicked,
+        };
+        self.runtime.send_message(player_chain, message.clone());
+        self.send_to_players(room, message);
+    }
+    
+    async fn enqueue_for_match(
+        &mut self,
+        player_chain: ChainId,
+        player_name: Option<String>,
+    ) -> Result<(), GameError> {
+        self.ensure_leaderboard_chain()?;
+        
+        let now = self.runtime.system_time().micros();
+        if self.state.matchmaking_queue.contains_key(&player_chain).await.map_err(|_| GameError::StorageError)? {
+            // Asking again keeps the place in the queue and retries matching with the bands
+            // widened since then
+            self.match_queue(now).await?;
+            if self.state.matchmaking_queue.contains_key(&player_chain).await.map_err(|_| GameError::StorageError)? {
+                return Err(GameError::AlreadyQueued);
+            }
+            return Ok(());
+        }
+        
+        // Store player name if provided
+        let player_name = player_name.and_then(|name| validate_player_name(&name).ok());
+        if let Some(ref name) = player_name {
+            let _ = self.state.player_names.insert(&player_chain, name.clone());
+        }
+        
+        let elo = self.runtime.application_parameters().elo;
+        let rating = self.load_player_stats(player_chain, &elo).await.elo_rating;
+        let entry = QueueEntry {
+            player_chain,
+            player_name,
+            rating,
+            queued_at: now,
+        };
+        self.state.matchmaking_queue.insert(&player_chain, entry)
+            .map_err(|_| GameError::StorageError)?;
+        eprintln!("[MATCHMAKING] Player {:?} queued with rating {}", player_chain, rating);
+        
+        self.match_queue(now).await
+    }
+    
+    async fn leave_queue(&mut self, player_chain: ChainId) -> Result<(), GameError> {
+        self.ensure_leaderboard_chain()?;
+        
+        if !self.state.matchmaking_queue.contains_key(&player_chain).await.map_err(|_| GameError::StorageError)? {
+            return Err(GameError::NotQueued);
+        }
+        self.state.matchmaking_queue.remove(&player_chain)
+            .map_err(|_| GameError::StorageError)?;
+        eprintln!("[MATCHMAKING] Player {:?} left the queue", player_chain);
+        
+        // Give whoever is still waiting a chance to match with their widened bands
+        let now = self.runtime.system_time().micros();
+        self.match_queue(now).await
+    }
+    
+    /// Pair waiting players whose rating bands overlap, longest waiting first, and start a
+    /// room for each pair. Bands widen with waiting time, so earlier arrivals that could not
+    /// be paired before may match now.
+    async fn match_queue(&mut self, now: u64) -> Result<(), GameError> {
+        let mut waiting = Vec::new();
+        for player_chain in self.state.matchmaking_queue.indices().await.map_err(|_| GameError::StorageError)? {
+            if let Some(entry) = self.state.matchmaking_queue.get(&player_chain).await.map_err(|_| GameError::StorageError)? {
+                waiting.push(entry);
+            }
+        }
+        waiting.sort_by_key(|entry| entry.queued_at);
+        
+        while !waiting.is_empty() {
+            let entry = waiting.remove(0);
+            
+            // Closest rating among the players this one can be paired with
+            let opponent_index = waiting.iter()
+                .enumerate()
+                .filter(|(_, other)| entry.accepts(other, now))
+                .min_by_key(|(_, other)| (entry.rating - other.rating).abs())
+                .map(|(index, _)| index);
+            
+            if let Some(index) = opponent_index {
+                let opponent = waiting.remove(index);
+                self.state.matchmaking_queue.remove(&entry.player_chain).map_err(|_| GameError::StorageError)?;
+                self.state.matchmaking_queue.remove(&opponent.player_chain).map_err(|_| GameError::StorageError)?;
+                self.start_match(entry, opponent, now).await?;
+            }
+        }
+        Ok(())
+    }
+    
+    /// Create a public room for two matched players and tell both they joined it
+    async fn start_match(&mut self, player1: QueueEntry, player2: QueueEntry, now: u64) -> Result<(), GameError> {
+        let room_id = self.allocate_room_id().await?;
+        let mut room = GameRoom::new(
+            room_id.clone(),
+            now,
+            false,
+            MatchFormat::default(),
+            DEFAULT_MOVE_TIMEOUT_SECS,
+            TimeoutPenalty::default(),
+        );
+        room.add_player(player1.player_chain, None)?;
+        room.add_player(player2.player_chain, None)?;
+        room.player1_name = player1.player_name;
+        room.player2_name = player2.player_name;
+        room.reset_deadline(now);
+        self.save_room(&room).await;
+        
+        eprintln!("[MATCHMAKING] Matched {:?} ({}) with {:?} ({}) in room {}",
+            player1.player_chain, player1.rating, player2.player_chain, player2.rating, room_id);
+        
+        for player_chain in [player1.player_chain, player2.player_chain] {
+            self.runtime.send_message(player_chain, GameMessage::PlayerJoined {
+                room_id: room_id.clone(),
+                player_chain,
+                error: None,
+            });
+        }
+        Ok(())
+    }
+    
+    async fn commit_choice(
+        &mut self,
+        room_id: &str,
+        player_chain: ChainId,
+        commitment: String,
+    ) -> Result<(), GameError> {
+        self.ensure_leaderboard_chain()?;
+        
+        let mut room = self.load_room(room_id).await?;
+        let room_id = room.room_id.clone();
+        room.commit_choice(player_chain, commitment)?;
+        eprintln!("[MESSAGE] Commitment recorded for player {:?} in room {}", player_chain, room_id);
+        
+        // Ask both players to reveal once both commitments are recorded
+        if room.both_players_committed() {
+            let now = self.runtime.system_time().micros();
+            room.reset_deadline(now);
+            
+            let reveal_message = GameMessage::RevealPhaseStarted {
+                room_id,
+                round_number: room.round_number,
+            };
+            
+            if let Some(player1) = room.player1 {
+                self.runtime.send_message(player1, reveal_message.clone());
+            }
+            if let Some(player2) = room.player2 {
+                self.runtime.send_message(player2, reveal_message);
+            }
+        }
+        
+        self.save_room(&room).await;
+        Ok(())
+    }
+    
+    async fn reveal_choice(
+        &mut self,
+        room_id: &str,
+        player_chain: ChainId,
+        choice: Choice,
+        salt: &str,
+    ) -> Result<(), GameError> {
+        self.ensure_leaderboard_chain()?;
+        
+        let mut room = self.load_room(room_id).await?;
+        let room_id = room.room_id.clone();
+        room.reveal_choice(player_chain, choice, salt)?;
+        eprintln!("[MESSAGE] Choice {:?} revealed for player {:?} in room {}", choice, player_chain, room_id);
+        
+        // Check if both players have revealed
+        if room.both_players_chose() {
+            eprintln!("[MESSAGE] Both players have revealed, calculating round result");
+            if let Some(outcome) = room.calculate_round_result() {
+                self.complete_round(&room_id, &mut room, outcome).await;
+            }
+        }
+        
+        self.save_room(&room).await;
+        Ok(())
+    }
+    
+    async fn claim_timeout(&mut self, room_id: &str) -> Result<(), GameError> {
+        self.ensure_leaderboard_chain()?;
+        
+        let mut room = self.load_room(room_id).await?;
+        let room_id = room.room_id.clone();
+        let now = self.runtime.system_time().micros();
+        if !room.deadline_passed(now) {
+            return Err(GameError::NoTimeoutToClaim);
+        }
+        
+        match room.timed_out_player(now) {
+            Some(loser) => {
+                eprintln!("[MESSAGE] Player {:?} missed the deadline in room {}", loser, room_id);
+                if let Some(outcome) = room.forfeit_round(loser) {
+                    self.complete_round(&room_id, &mut room, outcome).await;
+                }
+            }
+            None => {
+                eprintln!("[MESSAGE] Nobody acted before the deadline, room {} abandoned", room_id);
+                room.transition(RoomStatus::Abandoned)?;
+                
+                let closed_message = GameMessage::RoomClosed {
+                    room_id: room_id.clone(),
+                    status: room.status,
+                };
+                if let Some(player1) = room.player1 {
+                    self.runtime.send_message(player1, closed_message.clone());
+                }
+                if let Some(player2) = room.player2 {
+                    self.runtime.send_message(player2, closed_message);
+                }
+                
+                // Settled like a drawn game: stakes are refunded, series and tournament games
+                // are replayed and league games count as a draw
+                self.settle_game(&room_id, &mut room).await;
+            }
+        }
+        
+        self.save_room(&room).await;
+        Ok(())
+    }
+    
+    /// Notify both players of a resolved round (played or forfeited) and settle the game if it
+    /// is over
+    async fn complete_round(
+        &mut self,
+        room_id: &str,
+        room: &mut GameRoom,
+        (round_result, round_winner): (RoundResult, Option<ChainId>),
+    ) {
+        let now = self.runtime.system_time().micros();
+        room.reset_deadline(now);
+        
+        let Some(last_round) = room.round_history.last() else {
+            return;
+        };
+        
+        // Send round completed message to both players
+        let round_message = GameMessage::RoundCompleted {
+            room_id: room_id.to_string(),
+            player1_choice: last_round.player1_choice,
+            player2_choice: last_round.player2_choice,
+            round_winner,
+            round_result,
+            game_result: room.game_result.clone(),
+        };
+        
+        if let Some(player1) = room.player1 {
+            self.runtime.send_message(player1, round_message.clone());
+        }
+        if let Some(player2) = room.player2 {
+            self.runtime.send_message(player2, round_message.clone());
+        }
+        
+        // Check if game is finished
+        if room.game_result.is_finished {
+            self.settle_game(room_id, room).await;
+        }
+    }
+    
+    /// Announce the winner of a finished game to both players, record it on the leaderboard
+    /// and move its series on
+    async fn settle_game(&mut self, room_id: &str, room: &mut GameRoom) {
+        let Some(winner) = room.game_result.winner else {
+            // Nobody won the pot
+            for stake in room.take_stakes() {
+                self.refund_stake(&stake);
+            }
+            self.advance_series(room, None).await;
+            self.advance_tournament(room, None).await;
+            self.advance_league(room, None).await;
+            return;
+        };
+        eprintln!("[MESSAGE] Game finished! Winner: {:?}", winner);
+        
+        // Send game finished message
+        let game_finished_message = GameMessage::GameFinished {
+            room_id: room_id.to_string(),
+            winner,
+            final_result: room.game_result.clone(),
+        };
+        
+        if let Some(player1) = room.player1 {
+            self.runtime.send_message(player1, game_finished_message.clone());
+        }
+        if let Some(player2) = room.player2 {
+            self.runtime.send_message(player2, game_finished_message.clone());
+        }
+        
+        let stakes = room.take_stakes();
+        self.pay_prize(winner, stakes);
+        
+        // Update leaderboard stats only for non-private rooms
+        let loser = if room.player1 == Some(winner) { room.player2 } else { room.player1 };
+        if !room.private {
+            if let Some(loser) = loser {
+                let resigned = room.game_result.resigned_by == Some(loser);
+                self.record_game_result(room_id, winner, loser, resigned).await;
+            }
+        } else {
+            eprintln!(
+                "[LEADERBOARD] Skipping leaderboard update for private room {}", 
+                room_id
+            );
+        }
+        
+        self.advance_series(room, Some(winner)).await;
+        self.advance_tournament(room, Some(winner)).await;
+        self.advance_league(room, Some(winner)).await;
+    }
+    
+    /// Create a series between two players and open its first game
+    #[allow(clippy::too_many_arguments)]
+    async fn create_series(
+        &mut self,
+        player1: ChainId,
+        player2: ChainId,
+        best_of: u8,
+        private: bool,
+        format: Option<MatchFormat>,
+        move_timeout_secs: Option<u64>,
+        timeout_penalty: Option<TimeoutPenalty>,
+    ) -> Result<Series, GameError> {
+        self.ensure_leaderboard_chain()?;
+        
+        if player1 == player2 || best_of.is_multiple_of(2) {
+            return Err(GameError::InvalidSeries);
+        }
+        let format = format.unwrap_or_default();
+        format.validate()?;
+        
+        let counter = *self.state.series_counter.get() + 1;
+        self.state.series_counter.set(counter);
+        
+        let mut series = Series {
+            series_id: format!("series-{}", counter),
+            player1,
+            player2,
+            player1_name: self.state.player_names.get(&player1).await.ok().flatten(),
+            player2_name: self.state.player_names.get(&player2).await.ok().flatten(),
+            best_of,
+            player1_wins: 0,
+            player2_wins: 0,
+            rooms: Vec::new(),
+            private,
+            format,
+            move_timeout_secs: move_timeout_secs.unwrap_or(DEFAULT_MOVE_TIMEOUT_SECS),
+            timeout_penalty: timeout_penalty.unwrap_or_default(),
+            winner: None,
+            created_at: self.runtime.system_time().micros(),
+        };
+        self.start_series_game(&mut series).await?;
+        self.state.series.insert(&series.series_id, series.clone())
+            .map_err(|_| GameError::StorageError)?;
+        
+        eprintln!("[SERIES] Created {} (best of {}) between {:?} and {:?}",
+            series.series_id, best_of, player1, player2);
+        Ok(series)
+    }
+    
+    /// Open the next game of a series with both players seated and tell them they joined it
+    async fn start_series_game(&mut self, series: &mut Series) -> Result<String, GameError> {
+        let room_id = self.allocate_room_id().await?;
+        let now = self.runtime.system_time().micros();
+        let mut room = GameRoom::new(
+            room_id.clone(),
+            now,
+            series.private,
+            series.format,
+            series.move_timeout_secs,
+            series.timeout_penalty,
+        );
+        
+        let (player1, player2) = series.next_seats();
+        let name_of = |chain| if chain == series.player1 { series.player1_name.clone() } else { series.player2_name.clone() };
+        room.add_player(player1, None)?;
+        room.add_player(player2, None)?;
+        room.player1_name = name_of(player1);
+        room.player2_name = name_of(player2);
+        room.series_id = Some(series.series_id.clone());
+        room.previous_room = series.rooms.last().cloned();
+        room.reset_deadline(now);
+        self.save_room(&room).await;
+        series.rooms.push(room_id.clone());
+        
+        for player_chain in [player1, player2] {
+            self.runtime.send_message(player_chain, GameMessage::PlayerJoined {
+                room_id: room_id.clone(),
+                player_chain,
+                error: None,
+            });
+        }
+        Ok(room_id)
+    }
+    
+    /// Count a finished game towards its series: open the next game until a player clinches
+    /// the series, then report the series result. Drawn games are replayed.
+    async fn advance_series(&mut self, room: &mut GameRoom, winner: Option<ChainId>) {
+        let Some(series_id) = room.series_id.clone() else {
+            return;
+        };
+        let Ok(Some(mut series)) = self.state.series.get(&series_id).await else {
+            eprintln!("[SERIES] Series {} of room {} not found", series_id, room.room_id);
+            return;
+        };
+        
+        if winner.is_some_and(|winner| series.record_game(winner)) {
+            self.finish_series(&series).await;
+        } else {
+            match self.start_series_game(&mut series).await {
+                Ok(next_room) => room.next_room = Some(next_room),
+                Err(error) => eprintln!("[SERIES] Could not open the next game of {}: {:?}", series_id, error),
+            }
+        }
+        let _ = self.state.series.insert(&series_id, series);
+    }
+    
+    /// Create a tournament open for registration
+    #[allow(clippy::too_many_arguments)]
+    async fn create_tournament(
+        &mut self,
+        name: String,
+        format: TournamentFormat,
+        seeding: SeedingMethod,
+        max_players: Option<u32>,
+        players: Vec<ChainId>,
+        private: bool,
+        match_format: Option<MatchFormat>,
+        move_timeout_secs: Option<u64>,
+        timeout_penalty: Option<TimeoutPenalty>,
+        entry_fee: Option<Amount>,
+        currency: Option<Currency>,
+    ) -> Result<Tournament, GameError> {
+        self.ensure_leaderboard_chain()?;
+        let currency = currency.unwrap_or_default();
+        self.ensure_currency(currency)?;
+        
+        let name = name.trim().to_string();
+        let max_players = max_players.unwrap_or(MAX_TOURNAMENT_PLAYERS as u32);
+        if name.is_empty() || !(MIN_TOURNAMENT_PLAYERS..=MAX_TOURNAMENT_PLAYERS).contains(&(max_players as usize)) {
+            return Err(GameError::InvalidTournament);
+        }
+        let match_format = match_format.unwrap_or_default();
+        match_format.validate()?;
+        
+        let counter = *self.state.tournament_counter.get() + 1;
+        self.state.tournament_counter.set(counter);
+        
+        let mut tournament = Tournament::new(
+            format!("tournament-{}", counter),
+            name,
+            format,
+            seeding,
+            max_players,
+            private,
+            match_format,
+            move_timeout_secs.unwrap_or(DEFAULT_MOVE_TIMEOUT_SECS),
+            timeout_penalty.unwrap_or_default(),
+            self.runtime.system_time().micros(),
+        );
+        tournament.entry_fee = entry_fee.unwrap_or_default();
+        tournament.currency = currency;
+        for player in players {
+            tournament.register(player)?;
+        }
+        self.state.tournaments.insert(&tournament.tournament_id, tournament.clone())
+            .map_err(|_| GameError::StorageError)?;
+        
+        eprintln!("[TOURNAMENT] Created {} '{}' ({:?}, {:?} seeding)",
+            tournament.tournament_id, tournament.name, format, seeding);
+        Ok(tournament)
+    }
+    
+    async fn load_tournament(&self, tournament_id: &str) -> Result<Tournament, GameError> {
+        self.state.tournaments.get(tournament_id).await
+            .map_err(|_| GameError::StorageError)?
+            .ok_or(GameError::TournamentNotFound)
+    }
+    
+    async fn register_for_tournament(&mut self, tournament_id: &str, player_chain: ChainId, stake: Option<Stake>) -> Result<(), GameError> {
+        self.ensure_leaderboard_chain()?;
+        
+        let mut tournament = self.load_tournament(tournament_id).await?;
+        tournament.register_paid(player_chain, stake)?;
+        eprintln!("[TOURNAMENT] {:?} registered for {} ({} players)", player_chain, tournament_id, tournament.players.len());
+        self.state.tournaments.insert(tournament_id, tournament)
+            .map_err(|_| GameError::StorageError)
+    }
+    
+    /// Seed the registered players, build the bracket and open the first matches
+    async fn start_tournament(&mut self, tournament_id: &str) -> Result<Tournament, GameError> {
+        self.ensure_leaderboard_chain()?;
+        
+        let mut tournament = self.load_tournament(tournament_id).await?;
+        let now = self.runtime.system_time().micros();
+        let seeds = match tournament.seeding {
+            SeedingMethod::Random => tournament::shuffle_seeds(&tournament.players, &format!("{}:{}", tournament_id, now)),
+            SeedingMethod::Rating => {
+                // Stable sort keeps registration order between equally rated players
+                let elo = self.runtime.application_parameters().elo;
+                let mut rated = Vec::new();
+                for &player in &tournament.players {
+                    rated.push((self.load_player_stats(player, &elo).await.elo_rating, player));
+                }
+                rated.sort_by_key(|(rating, _)| std::cmp::Reverse(*rating));
+                rated.into_iter().map(|(_, player)| player).collect()
+            }
+        };
+        
+        let ready = tournament.start(seeds)?;
+        eprintln!("[TOURNAMENT] Started {} with {} players and {} bracket matches",
+            tournament_id, tournament.seeds.len(), tournament.matches.len());
+        self.start_tournament_matches(&mut tournament, ready).await?;
+        self.state.tournaments.insert(tournament_id, tournament.clone())
+            .map_err(|_| GameError::StorageError)?;
+        Ok(tournament)
+    }
+    
+    /// Close a tournament that never started, refunding the entry fees in escrow
+    async fn cancel_tournament(&mut self, tournament_id: &str) -> Result<Tournament, GameError> {
+        self.ensure_leaderboard_chain()?;
+        
+        let mut tournament = self.load_tournament(tournament_id).await?;
+        for stake in tournament.cancel()? {
+            self.refund_stake(&stake);
+        }
+        eprintln!("[TOURNAMENT] Cancelled {} with {} players registered", tournament_id, tournament.players.len());
+        for &player_chain in &tournament.players {
+            self.runtime.send_message(player_chain, GameMessage::TournamentFinished {
+                tournament_id: tournament_id.to_string(),
+                champion: None,
+            });
+        }
+        self.state.tournaments.insert(tournament_id, tournament.clone())
+            .map_err(|_| GameError::StorageError)?;
+        Ok(tournament)
+    }
+    
+    /// Open a room for each bracket match in `match_ids` and tell both players they joined it
+    async fn start_tournament_matches(&mut self, tournament: &mut Tournament, match_ids: Vec<u32>) -> Result<(), GameError> {
+        let now = self.runtime.system_time().micros();
+        for match_id in match_ids {
+            let bracket_match = &tournament.matches[match_id as usize];
+            let (Some(player1), Some(player2)) = (bracket_match.player1, bracket_match.player2) else {
+                continue;
+            };
+            
+            let room_id = self.allocate_room_id().await?;
+            let mut room = GameRoom::new(
+                room_id.clone(),
+                now,
+                tournament.private,
+                tournament.match_format,
+                tournament.move_timeout_secs,
+                tournament.timeout_penalty,
+            );
+            room.add_player(player1, None)?;
+            room.add_player(player2, None)?;
+            room.player1_name = self.state.player_names.get(&player1).await.ok().flatten();
+            room.player2_name = self.state.player_names.get(&player2).await.ok().flatten();
+            room.tournament_id = Some(tournament.tournament_id.clone());
+            room.reset_deadline(now);
+            self.save_room(&room).await;
+            tournament.matches[match_id as usize].room_id = Some(room_id.clone());
+            
+            eprintln!("[TOURNAMENT] {} match {} opened in room {}", tournament.tournament_id, match_id, room_id);
+            for player_chain in [player1, player2] {
+                self.runtime.send_message(player_chain, GameMessage::PlayerJoined {
+                    room_id: room_id.clone(),
+                    player_chain,
+                    error: None,
+                });
+            }
+        }
+        Ok(())
+    }
+    
+    /// Move a tournament on after one of its matches finished a game. The winner advances
+    /// in the bracket; a drawn game is replayed in a new room.
+    async fn advance_tournament(&mut self, room: &mut GameRoom, winner: Option<ChainId>) {
+        let Some(tournament_id) = room.tournament_id.clone() else {
+            return;
+        };
+        let Ok(Some(mut tournament)) = self.state.tournaments.get(&tournament_id).await else {
+            eprintln!("[TOURNAMENT] Tournament {} of room {} not found", tournament_id, room.room_id);
+            return;
+        };
+        let Some(match_id) = tournament.match_in_room(&room.room_id) else {
+            return;
+        };
+        
+        let ready = match winner {
+            Some(winner) => match tournament.record_result(match_id, winner) {
+                Ok(ready) => ready,
+                Err(error) => {
+                    eprintln!("[TOURNAMENT] Could not record match {} of {}: {:?}", match_id, tournament_id, error);
+                    return;
+                }
+            },
+            None => {
+                tournament.matches[match_id as usize].room_id = None;
+                vec![match_id]
+            }
+        };
+        if let Err(error) = self.start_tournament_matches(&mut tournament, ready).await {
+            eprintln!("[TOURNAMENT] Could not open the next matches of {}: {:?}", tournament_id, error);
+        }
+        if winner.is_none() {
+            room.next_room = tournament.matches[match_id as usize].room_id.clone();
+        }
+        
+        if tournament.status == TournamentStatus::Finished {
+            eprintln!("[TOURNAMENT] {} finished, champion: {:?}", tournament_id, tournament.champion);
+            let stakes = std::mem::take(&mut tournament.stakes);
+            match tournament.champion {
+                Some(champion) => self.pay_prize(champion, stakes),
+                None => stakes.iter().for_each(|stake| self.refund_stake(stake)),
+            }
+            for &player_chain in &tournament.players {
+                self.runtime.send_message(player_chain, GameMessage::TournamentFinished {
+                    tournament_id: tournament_id.clone(),
+                    champion: tournament.champion,
+                });
+            }
+        }
+        let _ = self.state.tournaments.insert(&tournament_id, tournament);
+    }
+    
+    /// Create a league open for registration
+    #[allow(clippy::too_many_arguments)]
+    async fn create_league(
+        &mut self,
+        name: String,
+        format: LeagueFormat,
+        rounds: Option<u32>,
+        max_players: Option<u32>,
+        players: Vec<ChainId>,
+        private: bool,
+        match_format: Option<MatchFormat>,
+        move_timeout_secs: Option<u64>,
+        timeout_penalty: Option<TimeoutPenalty>,
+    ) -> Result<League, GameError> {
+        self.ensure_leaderboard_chain()?;
+        
+        let name = name.trim().to_string();
+        let max_players = max_players.unwrap_or(MAX_LEAGUE_PLAYERS as u32);
+        if name.is_empty() || !(MIN_LEAGUE_PLAYERS..=MAX_LEAGUE_PLAYERS).contains(&(max_players as usize)) {
+            return Err(GameError::InvalidLeague);
+        }
+        let match_format = match_format.unwrap_or_default();
+        match_format.validate()?;
+        
+        let counter = *self.state.league_counter.get() + 1;
+        self.state.league_counter.set(counter);
+        
+        let mut league = League::new(
+            format!("league-{}", counter),
+            name,
+            format,
+            rounds.filter(|&rounds| rounds > 0),
+            max_players,
+            private,
+            match_format,
+            move_timeout_secs.unwrap_or(DEFAULT_MOVE_TIMEOUT_SECS),
+            timeout_penalty.unwrap_or_default(),
+            self.runtime.system_time().micros(),
+        );
+        for player in players {
+            league.register(player)?;
+        }
+        self.state.leagues.insert(&league.league_id, league.clone())
+            .map_err(|_| GameError::StorageError)?;
+        
+        eprintln!("[LEAGUE] Created {} '{}' ({:?})", league.league_id, league.name, format);
+        Ok(league)
+    }
+    
+    async fn load_league(&self, league_id: &str) -> Result<League, GameError> {
+        self.state.leagues.get(league_id).await
+            .map_err(|_| GameError::StorageError)?
+            .ok_or(GameError::LeagueNotFound)
+    }
+    
+    async fn register_for_league(&mut self, league_id: &str, player_chain: ChainId) -> Result<(), GameError> {
+        self.ensure_leaderboard_chain()?;
+        
+        let mut league = self.load_league(league_id).await?;
+        league.register(player_chain)?;
+        eprintln!("[LEAGUE] {:?} registered for {} ({} players)", player_chain, league_id, league.players.len());
+        self.state.leagues.insert(league_id, league)
+            .map_err(|_| GameError::StorageError)
+    }
+    
+    /// Seed the registered players by Elo rating and open the games of the first round
+    async fn start_league(&mut self, league_id: &str) -> Result<League, GameError> {
+        self.ensure_leaderboard_chain()?;
+        
+        let mut league = self.load_league(league_id).await?;
+        let elo = self.runtime.application_parameters().elo;
+        let mut rated = Vec::new();
+        for &player in &league.players {
+            rated.push((self.load_player_stats(player, &elo).await.elo_rating, player));
+        }
+        rated.sort_by_key(|(rating, _)| std::cmp::Reverse(*rating));
+        
+        let games = league.start(rated.into_iter().map(|(_, player)| player).collect())?;
+        eprintln!("[LEAGUE] Started {} with {} players over {} rounds", league_id, league.players.len(), league.rounds);
+        self.start_league_games(&mut league, games).await?;
+        self.state.leagues.insert(league_id, league.clone())
+            .map_err(|_| GameError::StorageError)?;
+        Ok(league)
+    }
+    
+    /// Open a room for each pairing in `indices` and tell both players they joined it
+    async fn start_league_games(&mut self, league: &mut League, indices: Vec<usize>) -> Result<(), GameError> {
+        let now = self.runtime.system_time().micros();
+        for index in indices {
+            let pairing = &league.pairings[index];
+            let (player1, Some(player2)) = (pairing.player1, pairing.player2) else {
+                continue;
+            };
+            
+            let room_id = self.allocate_room_id().await?;
+            let mut room = GameRoom::new(
+                room_id.clone(),
+                now,
+                league.private,
+                league.match_format,
+                league.move_timeout_secs,
+                league.timeout_penalty,
+            );
+            room.add_player(player1, None)?;
+            room.add_player(player2, None)?;
+            room.player1_name = self.state.player_names.get(&player1).await.ok().flatten();
+            room.player2_name = self.state.player_names.get(&player2).await.ok().flatten();
+            room.league_id = Some(league.league_id.clone());
+            room.reset_deadline(now);
+            self.save_room(&room).await;
+            league.pairings[index].room_id = Some(room_id.clone());
+            
+            eprintln!("[LEAGUE] {} round {} game opened in room {}", league.league_id, league.current_round, room_id);
+            for player_chain in [player1, player2] {
+                self.runtime.send_message(player_chain, GameMessage::PlayerJoined {
+                    room_id: room_id.clone(),
+                    player_chain,
+                    error: None,
+                });
+            }
+        }
+        Ok(())
+    }
+    
+    /// Record a finished league game, drawn games included, and pair the next round once the
+    /// current one is complete
+    async fn advance_league(&mut self, room: &GameRoom, winner: Option<ChainId>) {
+        let Some(league_id) = room.league_id.clone() else {
+            return;
+        };
+        let Ok(Some(mut league)) = self.state.leagues.get(&league_id).await else {
+            eprintln!("[LEAGUE] League {} of room {} not found", league_id, room.room_id);
+            return;
+        };
+        let Some(index) = league.pairing_in_room(&room.room_id) else {
+            return;
+        };
+        
+        match league.record_result(index, winner) {
+            Ok(games) => {
+                if let Err(error) = self.start_league_games(&mut league, games).await {
+                    eprintln!("[LEAGUE] Could not open the games of round {} of {}: {:?}", league.current_round, league_id, error);
+                }
+            }
+            Err(error) => {
+                eprintln!("[LEAGUE] Could not record the game in room {}: {:?}", room.room_id, error);
+                return;
+            }
+        }
+        
+        if league.status == LeagueStatus::Finished {
+            let winner = league.winner();
+            eprintln!("[LEAGUE] {} finished, winner: {:?}", league_id, winner);
+            for &player_chain in &league.players {
+                self.runtime.send_message(player_chain, GameMessage::LeagueFinished {
+                    league_id: league_id.clone(),
+                    winner,
+                });
+            }
+        }
+        let _ = self.state.leagues.insert(&league_id, league);
+    }
+    
+    /// Announce the series winner to both players and record the series on the leaderboard
+    async fn finish_series(&mut self, series: &Series) {
+        let Some(winner) = series.winner else {
+            return;
+        };
+        eprintln!("[SERIES] {} won by {:?} ({}-{})",
+            series.series_id, winner, series.player1_wins, series.player2_wins);
+        
+        let message = GameMessage::SeriesFinished {
+            series_id: series.series_id.clone(),
+            winner,
+            player1_wins: series.player1_wins,
+            player2_wins: series.player2_wins,
+        };
+        self.runtime.send_message(series.player1, message.clone());
+        self.runtime.send_message(series.player2, message);
+        
+        if series.private {
+            return;
+        }
+        let elo = self.runtime.application_parameters().elo;
+        for player_chain in [series.player1, series.player2] {
+            let mut stats = self.load_player_stats(player_chain, &elo).await;
+            stats.series_played += 1;
+            if player_chain == winner {
+                stats.series_won += 1;
+            }
+            self.index_player(&stats).await;
+            let _ = self.state.player_stats.insert(&player_chain, stats);
+        }
+    }
+    
+    /// Persist a room and keep `available_rooms` in sync with its status. A closed room
+    /// releases its alias for new rooms.
+    async fn save_room(&mut self, room: &GameRoom) {
+        let _ = self.state.rooms.insert(&room.room_id, room.clone());
+        let _ = if room.status.is_closed() {
+            self.state.open_rooms.remove(&room.room_id)
+        } else {
+            self.state.open_rooms.insert(&room.room_id, room.status)
+        };
+        if let Some(alias) = room.alias.as_ref().filter(|_| room.status.is_closed()) {
+            // Closed rooms are saved again, e.g. on a rematch offer, when a newer room may hold the alias
+            if self.state.room_aliases.get(alias).await.ok().flatten().as_ref() == Some(&room.room_id) {
+                let _ = self.state.room_aliases.remove(alias);
+            }
+        }
+        
+        let available_rooms = self.state.available_rooms.get();
+        let listed = available_rooms.contains(&room.room_id);
+        if room.is_listed() && !listed {
+            let mut available_rooms = available_rooms.clone();
+            available_rooms.push(room.room_id.clone());
+            self.state.available_rooms.set(available_rooms);
+        } else if !room.is_listed() && listed {
+            let mut available_rooms = available_rooms.clone();
+            available_rooms.retain(|id| id != &room.room_id);
+            self.state.available_rooms.set(available_rooms);
+        }
+    }
+    
+    /// Get a player's leaderboard stats, or fresh stats at the configured initial rating
+    async fn load_player_stats(&self, player_chain: ChainId, elo: &EloConfig) -> PlayerStats {
+        match self.state.player_stats.get(&player_chain).await {
+            Ok(Some(existing_stats)) => existing_stats,
+            _ => PlayerStats {
+                elo_rating: elo.initial_rating,
+                ..PlayerStats::new(player_chain)
+            },
+        }
+    }
+    
+    /// Record a finished public game on the leaderboard, rating both players from their
+    /// ratings before the game
+    async fn record_game_result(&mut self, room_id: &str, winner: ChainId, loser: ChainId, resigned: bool) {
+        let params = self.runtime.application_parameters();
+        let (elo, glicko2) = (params.elo, params.glicko2);
+        let timestamp = self.runtime.system_time().micros();
+        
+        let winner_stats = self.load_player_stats(winner, &elo).await;
+        let loser_stats = self.load_player_stats(loser, &elo).await;
+        let winner_delta = elo.rating_delta(winner_stats.elo_rating, loser_stats.elo_rating, winner_stats.games_played, 1.0);
+        let loser_delta = elo.rating_delta(loser_stats.elo_rating, winner_stats.elo_rating, loser_stats.games_played, 0.0);
+        
+        // Glicko-2 ratings including deviation growth from inactivity
+        let winner_glicko2 = winner_stats.current_glicko2(timestamp, &glicko2);
+        let loser_glicko2 = loser_stats.current_glicko2(timestamp, &glicko2);
+        let winner_glicko2_next = winner_glicko2.update(&loser_glicko2, 1.0, &glicko2);
+        let loser_glicko2_next = loser_glicko2.update(&winner_glicko2, 0.0, &glicko2);
+        
+        for (mut stats, opponent, won, delta, next_glicko2) in [
+            (winner_stats, loser, true, winner_delta, winner_glicko2_next),
+            (loser_stats, winner, false, loser_delta, loser_glicko2_next),
+        ] {
+            let rating_before = stats.elo_rating;
+            stats.add_game(won, timestamp);
+            if !won && resigned {
+                stats.resignations += 1;
+            }
+            stats.elo_rating = rating_before + delta;
+            stats.glicko2 = next_glicko2;
+            
+            let mut history = self.state.rating_history.get(&stats.chain_id).await
+                .ok()
+                .flatten()
+                .unwrap_or_default();
+            history.push(RatingChange {
+                timestamp,
+                room_id: room_id.to_string(),
+                opponent,
+                won,
+                rating_before,
+                rating_after: stats.elo_rating,
+            });
+            if history.len() > RATING_HISTORY_LIMIT {
+                history.drain(..history.len() - RATING_HISTORY_LIMIT);
+            }
+            let _ = self.state.rating_history.insert(&stats.chain_id, history);
+            
+            eprintln!("[LEADERBOARD] Updated stats for {:?}: games={}, wins={}, losses={}, rating {} -> {}, glicko2 {:.0} ± {:.0}", 
+                stats.chain_id, stats.games_played, stats.games_won, stats.games_lost, rating_before, stats.elo_rating,
+                stats.glicko2.rating, stats.glicko2.deviation);
+            let player_chain = stats.chain_id;
+            if won {
+                self.credit_win_reward(player_chain, stats.current_streak, timestamp).await;
+            }
+            self.index_player(&stats).await;
+            let _ = self.state.player_stats.insert(&player_chain, stats);
+        }
+    }
+    
+    /// Credit reward points for a public win that brought the winner's streak to `streak`
+    async fn credit_win_reward(&mut self, player_chain: ChainId, streak: u64, timestamp: u64) {
+        let config = self.runtime.application_parameters().rewards;
+        let mut balance = self.state.reward_balances.get(&player_chain).await
+            .ok()
+            .flatten()
+            .unwrap_or_else(|| RewardBalance::new(player_chain));
+        let points = balance.credit_win(streak, timestamp, &config);
+        eprintln!("[REWARDS] Credited {} points to {:?} for a {} game streak (balance {}, {} today)",
+            points, player_chain, streak, balance.balance, balance.earned_today);
+        let _ = self.state.reward_balances.insert(&player_chain, balance);
+    }
+    
+    async fn update_leaderboard_stats(&mut self, player_chain: ChainId, won: bool) {
+        eprintln!("[LEADERBOARD] Updating stats for {:?}, won: {}", player_chain, won);
+        
+        let timestamp = self.runtime.system_time().micros();
+        let elo = self.runtime.application_parameters().elo;
+        
+        // Get or create player stats
+        let mut stats = self.load_player_stats(player_chain, &elo).await;
+        
+        // Update stats
+        stats.add_game(won, timestamp);
+        
+        // Save updated stats
+        self.index_player(&stats).await;
+        let _ = self.state.player_stats.insert(&player_chain, stats.clone());
+        
+        eprintln!("[LEADERBOARD] Updated stats for {:?}: games={}, wins={}, losses={}, win_rate={:.2}%", 
+            player_chain, stats.games_played, stats.games_won, stats.games_lost, stats.win_rate());
+    }
+    
+    /// Reposition a player on the leaderboard after their stats changed
+    async fn index_player(&mut self, stats: &PlayerStats) {
+        let elo = self.runtime.application_parameters().elo;
+        let entry = LeaderboardEntry {
+            chain_id: stats.chain_id,
+            wins: stats.games_won,
+            losses: stats.games_lost,
+            total_games: stats.games_played,
+            player_name: None, // Looked up when the leaderboard is read
+            rating: stats.elo_rating,
+            provisional: elo.is_provisional(stats.games_played),
+            series_played: stats.series_played,
+            series_won: stats.series_won,
+        };
+        if self.state.index_leaderboard_entry(entry).await.is_err() {
+            eprintln!("[LEADERBOARD] Failed to index {:?}", stats.chain_id);
+        }
+    }
+}
+
+#[ComplexObject]
+impl RockPaperScissorsState {}
 
 #[ComplexObject]
-impl RockPaperScissorsState {}
\ No newline at end of file
+impl PlayerStats {}
\ No newline at end of file
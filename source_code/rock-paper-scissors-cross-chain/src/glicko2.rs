@@ -0,0 +1,246 @@
+// Copyright (c) Zefchain Labs, Inc.
+// SPDX-License-Identifier: Apache-2.0
+
+/*! Glicko-2 ratings for the competitive ladder
+
+Each finished game is treated as its own rating period. Between games a player's rating
+deviation grows with the number of rating periods they sat out, so returning players are
+rated with less confidence than active ones.
+*/
+
+use async_graphql::{ComplexObject, SimpleObject};
+use serde::{Deserialize, Serialize};
+
+/// Rating given to players before their first game
+pub const DEFAULT_RATING: f64 = 1500.0;
+/// Rating deviation of an unrated player, also the upper bound for inactivity growth
+pub const DEFAULT_DEVIATION: f64 = 350.0;
+/// Volatility of an unrated player
+pub const DEFAULT_VOLATILITY: f64 = 0.06;
+
+/// Conversion factor between the Glicko and Glicko-2 scales
+const SCALE: f64 = 173.7178;
+/// Convergence tolerance of the volatility iteration
+const EPSILON: f64 = 0.000_001;
+/// z-score of a 95% confidence interval
+const CONFIDENCE_Z: f64 = 1.96;
+
+// Glicko-2 system configuration
+#[derive(Debug, Clone, Copy, Deserialize, Serialize)]
+#[serde(default)]
+pub struct Glicko2Config {
+    pub tau: f64, // Constrains how fast volatility changes
+    pub rating_period_secs: u64, // Inactivity needed for one period of deviation growth
+}
+
+impl Default for Glicko2Config {
+    fn default() -> Self {
+        Self {
+            tau: 0.5,
+            rating_period_secs: 24 * 60 * 60,
+        }
+    }
+}
+
+// A player's Glicko-2 rating on the Glicko scale
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, SimpleObject)]
+#[graphql(complex)]
+pub struct Glicko2Rating {
+    pub rating: f64,
+    pub deviation: f64, // Rating deviation (RD)
+    pub volatility: f64,
+}
+
+// Range the true rating lies in with 95% confidence
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, SimpleObject)]
+pub struct RatingInterval {
+    pub low: f64,
+    pub high: f64,
+}
+
+impl Default for Glicko2Rating {
+    fn default() -> Self {
+        Self {
+            rating: DEFAULT_RATING,
+            deviation: DEFAULT_DEVIATION,
+            volatility: DEFAULT_VOLATILITY,
+        }
+    }
+}
+
+#[ComplexObject]
+impl Glicko2Rating {
+    /// 95% confidence interval around the rating
+    async fn interval(&self) -> RatingInterval {
+        self.confidence_interval()
+    }
+}
+
+impl Glicko2Rating {
+    /// 95% confidence interval around the rating
+    pub fn confidence_interval(&self) -> RatingInterval {
+        RatingInterval {
+            low: self.rating - CONFIDENCE_Z * self.deviation,
+            high: self.rating + CONFIDENCE_Z * self.deviation,
+        }
+    }
+    
+    /// Rating after `inactive_micros` without games: the deviation grows by one step per
+    /// full rating period, capped at the deviation of an unrated player
+    pub fn decayed(&self, inactive_micros: u64, config: &Glicko2Config) -> Self {
+        let period_micros = config.rating_period_secs.max(1).saturating_mul(1_000_000);
+        let periods = (inactive_micros / period_micros) as f64;
+        if periods == 0.0 {
+            return *self;
+        }
+        
+        let phi = self.deviation / SCALE;
+        let phi = (phi * phi + periods * self.volatility * self.volatility).sqrt();
+        Self {
+            deviation: (phi * SCALE).min(DEFAULT_DEVIATION),
+            ..*self
+        }
+    }
+    
+    /// Rating after one game against `opponent` with `score` 1 for a win, 0.5 for a draw
+    /// and 0 for a loss. Both ratings should already include inactivity decay.
+    pub fn update(&self, opponent: &Self, score: f64, config: &Glicko2Config) -> Self {
+        self.update_period(&[(*opponent, score)], config)
+    }
+    
+    /// Rating after a rating period with `results`, each an opponent and the score against
+    /// them, following steps 3 to 8 of the Glicko-2 paper. A period without games leaves
+    /// the rating as it is; inactivity is handled by `decayed`. The contract rates every
+    /// game as its own period through `update`, so only one result is ever passed on chain.
+    pub fn update_period(&self, results: &[(Self, f64)], config: &Glicko2Config) -> Self {
+        if results.is_empty() {
+            return *self;
+        }
+        let mu = (self.rating - DEFAULT_RATING) / SCALE;
+        let phi = self.deviation / SCALE;
+        
+        let mut inverse_variance = 0.0;
+        let mut improvement = 0.0;
+        for (opponent, score) in results {
+            let mu_j = (opponent.rating - DEFAULT_RATING) / SCALE;
+            let phi_j = opponent.deviation / SCALE;
+            let g = 1.0 / (1.0 + 3.0 * phi_j * phi_j / (std::f64::consts::PI * std::f64::consts::PI)).sqrt();
+            let expected = 1.0 / (1.0 + (-g * (mu - mu_j)).exp());
+            inverse_variance += g * g * expected * (1.0 - expected);
+            improvement += g * (score - expected);
+        }
+        let variance = 1.0 / inverse_variance;
+        let delta = variance * improvement;
+        
+        let volatility = Self::next_volatility(phi, self.volatility, variance, delta, config.tau);
+        
+        let phi_star = (phi * phi + volatility * volatility).sqrt();
+        let phi_next = 1.0 / (1.0 / (phi_star * phi_star) + 1.0 / variance).sqrt();
+        let mu_next = mu + phi_next * phi_next * improvement;
+        
+        Self {
+            rating: mu_next * SCALE + DEFAULT_RATING,
+            deviation: (phi_next * SCALE).min(DEFAULT_DEVIATION),
+            volatility,
+        }
+    }
+    
+    /// New volatility, found with the Illinois algorithm from step 5 of the Glicko-2 paper
+    fn next_volatility(phi: f64, sigma: f64, variance: f64, delta: f64, tau: f64) -> f64 {
+        let a = (sigma * sigma).ln();
+        let f = |x: f64| {
+            let ex = x.exp();
+            let denom = phi * phi + variance + ex;
+            ex * (delta * delta - denom) / (2.0 * denom * denom) - (x - a) / (tau * tau)
+        };
+        
+        let mut big_a = a;
+        let mut big_b = if delta * delta > phi * phi + variance {
+            (delta * delta - phi * phi - variance).ln()
+        } else {
+            let mut k = 1.0;
+            while f(a - k * tau) < 0.0 {
+                k += 1.0;
+            }
+            a - k * tau
+        };
+        
+        let mut f_a = f(big_a);
+        let mut f_b = f(big_b);
+        while (big_b - big_a).abs() > EPSILON {
+            let big_c = big_a + (big_a - big_b) * f_a / (f_b - f_a);
+            let f_c = f(big_c);
+            if f_c * f_b <= 0.0 {
+                big_a = big_b;
+                f_a = f_b;
+            } else {
+                f_a /= 2.0;
+            }
+            big_b = big_c;
+            f_b = f_c;
+        }
+        
+        (big_a / 2.0).exp()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    
+    fn rating(rating: f64, deviation: f64) -> Glicko2Rating {
+        Glicko2Rating { rating, deviation, volatility: DEFAULT_VOLATILITY }
+    }
+    
+    fn assert_close(actual: f64, expected: f64, tolerance: f64) {
+        assert!((actual - expected).abs() <= tolerance, "{} is not within {} of {}", actual, tolerance, expected);
+    }
+    
+    /// Worked example of section 3 of Glickman's "Example of the Glicko-2 system"
+    #[test]
+    fn glickman_example() {
+        let config = Glicko2Config { tau: 0.5, ..Glicko2Config::default() };
+        let player = rating(1500.0, 200.0);
+        let results = [
+            (rating(1400.0, 30.0), 1.0),
+            (rating(1550.0, 100.0), 0.0),
+            (rating(1700.0, 300.0), 0.0),
+        ];
+        
+        let updated = player.update_period(&results, &config);
+        assert_close(updated.rating, 1464.06, 0.01);
+        assert_close(updated.deviation, 151.52, 0.01);
+        assert_close(updated.volatility, 0.05999, 0.00001);
+    }
+    
+    #[test]
+    fn single_game_update() {
+        let config = Glicko2Config::default();
+        let player = rating(1500.0, 200.0);
+        let opponent = rating(1400.0, 30.0);
+        assert_eq!(player.update(&opponent, 1.0, &config), player.update_period(&[(opponent, 1.0)], &config));
+        assert!(player.update(&opponent, 1.0, &config).rating > player.rating);
+        assert!(player.update(&opponent, 0.0, &config).rating < player.rating);
+        assert_eq!(player.update_period(&[], &config), player);
+    }
+    
+    #[test]
+    fn inactivity_decay() {
+        let config = Glicko2Config::default();
+        let period = config.rating_period_secs * 1_000_000;
+        let player = rating(1500.0, 200.0);
+        
+        // Only full rating periods count
+        assert_eq!(player.decayed(period - 1, &config), player);
+        
+        // One period grows the deviation to phi* of step 6 of the example
+        let decayed = player.decayed(period, &config);
+        assert_close(decayed.deviation, 200.27, 0.01);
+        assert_eq!(decayed.rating, player.rating);
+        assert_eq!(decayed.volatility, player.volatility);
+        
+        let longer = player.decayed(10 * period, &config);
+        assert!(longer.deviation > decayed.deviation);
+        assert_eq!(player.decayed(u64::MAX, &config).deviation, DEFAULT_DEVIATION);
+    }
+}
@@ -0,0 +1,570 @@
+// Copyright (c) Zefchain Labs, Inc.
+// SPDX-License-Identifier: Apache-2.0
+
+/*! Swiss-system and round-robin leagues
+
+A league plays in rounds. Each round pairs every player once, or gives them a bye when the
+field is odd, and the next round is paired as soon as the last game of the current one is
+over. A win or a bye scores one point, a drawn game half a point.
+*/
+
+use async_graphql::{ComplexObject, Enum, SimpleObject};
+use linera_sdk::linera_base_types::ChainId;
+use serde::{Deserialize, Serialize};
+
+use crate::{GameError, MatchFormat, TimeoutPenalty};
+
+/// Smallest field a league can start with
+pub const MIN_LEAGUE_PLAYERS: usize = 2;
+/// Largest field a league accepts
+pub const MAX_LEAGUE_PLAYERS: usize = 64;
+
+/// Pairing attempts before a Swiss round gives up on avoiding repeat pairings
+const SWISS_SEARCH_BUDGET: u32 = 10_000;
+
+// Schedule of a league
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq, Default, Enum)]
+pub enum LeagueFormat {
+    #[default]
+    Swiss, // Players with equal scores meet, never twice
+    RoundRobin, // Everyone meets everyone once
+}
+
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq, Enum)]
+pub enum LeagueStatus {
+    Registration, // Accepting players
+    InProgress,
+    Finished,
+}
+
+// One game of a league round, or a bye when `player2` is empty
+#[derive(Debug, Clone, Serialize, Deserialize, SimpleObject)]
+pub struct LeaguePairing {
+    pub round: u32,
+    pub player1: ChainId,
+    pub player2: Option<ChainId>,
+    pub room_id: Option<String>,
+    pub winner: Option<ChainId>, // Empty for a draw once finished
+    pub finished: bool,
+}
+
+// Row of the standings table
+#[derive(Debug, Clone, Serialize, Deserialize, SimpleObject)]
+pub struct LeagueStanding {
+    pub rank: u32,
+    pub player: ChainId,
+    pub points: f64,
+    pub wins: u32,
+    pub draws: u32,
+    pub losses: u32,
+    pub byes: u32,
+    pub buchholz: f64, // Sum of the opponents' points, the first tiebreak
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, SimpleObject)]
+#[graphql(complex)]
+pub struct League {
+    pub league_id: String,
+    pub name: String,
+    pub format: LeagueFormat,
+    pub status: LeagueStatus,
+    pub max_players: u32,
+    pub players: Vec<ChainId>, // In registration order, then by seed once started
+    pub rounds: u32, // Number of rounds, set when the league starts unless configured
+    pub current_round: u32, // 0 until the league starts
+    pub pairings: Vec<LeaguePairing>,
+    pub private: bool, // Games are private rooms and not recorded on the leaderboard
+    pub match_format: MatchFormat, // Format of each game
+    pub move_timeout_secs: u64,
+    pub timeout_penalty: TimeoutPenalty,
+    pub created_at: u64,
+}
+
+#[ComplexObject]
+impl League {
+    /// Standings table, best player first
+    async fn standings(&self) -> Vec<LeagueStanding> {
+        self.compute_standings()
+    }
+    
+    /// Pairings of one round, the current round by default
+    async fn round_pairings(&self, round: Option<u32>) -> Vec<LeaguePairing> {
+        let round = round.unwrap_or(self.current_round);
+        self.pairings.iter().filter(|pairing| pairing.round == round).cloned().collect()
+    }
+}
+
+impl LeaguePairing {
+    pub fn is_bye(&self) -> bool {
+        self.player2.is_none()
+    }
+    
+    /// Points scored by `player` in this pairing
+    fn points(&self, player: ChainId) -> f64 {
+        match self.winner {
+            Some(winner) if winner == player => 1.0,
+            Some(_) => 0.0,
+            None => 0.5,
+        }
+    }
+    
+    fn opponent(&self, player: ChainId) -> Option<ChainId> {
+        if self.player1 == player {
+            self.player2
+        } else if self.player2 == Some(player) {
+            Some(self.player1)
+        } else {
+            None
+        }
+    }
+}
+
+impl League {
+    #[allow(clippy::too_many_arguments)]
+    pub fn new(
+        league_id: String,
+        name: String,
+        format: LeagueFormat,
+        rounds: Option<u32>,
+        max_players: u32,
+        private: bool,
+        match_format: MatchFormat,
+        move_timeout_secs: u64,
+        timeout_penalty: TimeoutPenalty,
+        created_at: u64,
+    ) -> Self {
+        Self {
+            league_id,
+            name,
+            format,
+            status: LeagueStatus::Registration,
+            max_players,
+            players: Vec::new(),
+            rounds: rounds.unwrap_or(0),
+            current_round: 0,
+            pairings: Vec::new(),
+            private,
+            match_format,
+            move_timeout_secs,
+            timeout_penalty,
+            created_at,
+        }
+    }
+    
+    pub fn register(&mut self, player: ChainId) -> Result<(), GameError> {
+        if self.status != LeagueStatus::Registration {
+            return Err(GameError::LeagueNotOpen);
+        }
+        if self.players.contains(&player) {
+            return Err(GameError::AlreadyRegistered);
+        }
+        if self.players.len() >= self.max_players as usize {
+            return Err(GameError::LeagueFull);
+        }
+        self.players.push(player);
+        Ok(())
+    }
+    
+    /// Close registration with the players ordered by `seeds`, best first, and pair the
+    /// first round. Returns the indices of the pairings that need a room.
+    pub fn start(&mut self, seeds: Vec<ChainId>) -> Result<Vec<usize>, GameError> {
+        if self.status != LeagueStatus::Registration {
+            return Err(GameError::LeagueNotOpen);
+        }
+        if seeds.len() < MIN_LEAGUE_PLAYERS {
+            return Err(GameError::NotEnoughPlayers);
+        }
+        
+        // A round robin needs one round per opponent, plus one for the byes of an odd field.
+        // Swiss leagues default to enough rounds to leave a single unbeaten player.
+        let round_robin_rounds = (seeds.len() + seeds.len() % 2 - 1) as u32;
+        self.rounds = match self.format {
+            LeagueFormat::RoundRobin => round_robin_rounds,
+            LeagueFormat::Swiss if self.rounds == 0 => seeds.len().next_power_of_two().trailing_zeros(),
+            LeagueFormat::Swiss => self.rounds.min(round_robin_rounds),
+        };
+        self.players = seeds;
+        self.status = LeagueStatus::InProgress;
+        Ok(self.start_round())
+    }
+    
+    /// Pairing currently played in `room_id`
+    pub fn pairing_in_room(&self, room_id: &str) -> Option<usize> {
+        self.pairings.iter().position(|pairing| !pairing.finished && pairing.room_id.as_deref() == Some(room_id))
+    }
+    
+    /// Record the result of a game, `None` for a draw. Once the round is complete the next
+    /// one is paired; returns the indices of the new pairings that need a room.
+    pub fn record_result(&mut self, index: usize, winner: Option<ChainId>) -> Result<Vec<usize>, GameError> {
+        let pairing = self.pairings.get_mut(index).ok_or(GameError::InvalidRoomState)?;
+        if pairing.finished {
+            return Err(GameError::InvalidRoomState);
+        }
+        if winner.is_some_and(|winner| winner != pairing.player1 && Some(winner) != pairing.player2) {
+            return Err(GameError::NotAPlayer);
+        }
+        pairing.winner = winner;
+        pairing.finished = true;
+        
+        if self.pairings.iter().any(|pairing| !pairing.finished) {
+            return Ok(Vec::new());
+        }
+        Ok(self.start_round())
+    }
+    
+    /// Leader of the standings once the league is over
+    pub fn winner(&self) -> Option<ChainId> {
+        if self.status != LeagueStatus::Finished {
+            return None;
+        }
+        self.compute_standings().first().map(|standing| standing.player)
+    }
+    
+    /// Standings sorted by points, then Buchholz, then wins, then seed
+    pub fn compute_standings(&self) -> Vec<LeagueStanding> {
+        let mut standings: Vec<LeagueStanding> = self.players.iter()
+            .map(|&player| LeagueStanding {
+                rank: 0,
+                player,
+                points: 0.0,
+                wins: 0,
+                draws: 0,
+                losses: 0,
+                byes: 0,
+                buchholz: 0.0,
+            })
+            .collect();
+        
+        for pairing in self.pairings.iter().filter(|pairing| pairing.finished) {
+            for standing in standings.iter_mut() {
+                if pairing.is_bye() && pairing.player1 == standing.player {
+                    standing.byes += 1;
+                    standing.points += 1.0;
+                } else if pairing.opponent(standing.player).is_some() {
+                    match pairing.winner {
+                        Some(winner) if winner == standing.player => standing.wins += 1,
+                        Some(_) => standing.losses += 1,
+                        None => standing.draws += 1,
+                    }
+                    standing.points += pairing.points(standing.player);
+                }
+            }
+        }
+        
+        let points_of = |player: ChainId, standings: &[LeagueStanding]| {
+            standings.iter().find(|standing| standing.player == player).map_or(0.0, |standing| standing.points)
+        };
+        let buchholz: Vec<f64> = standings.iter()
+            .map(|standing| {
+                self.pairings.iter()
+                    .filter(|pairing| pairing.finished)
+                    .filter_map(|pairing| pairing.opponent(standing.player))
+                    .map(|opponent| points_of(opponent, &standings))
+                    .sum()
+            })
+            .collect();
+        for (standing, buchholz) in standings.iter_mut().zip(buchholz) {
+            standing.buchholz = buchholz;
+        }
+        
+        // Sorting is stable, so seed order breaks the remaining ties
+        standings.sort_by(|a, b| {
+            b.points.total_cmp(&a.points)
+                .then_with(|| b.buchholz.total_cmp(&a.buchholz))
+                .then_with(|| b.wins.cmp(&a.wins))
+        });
+        for (rank, standing) in standings.iter_mut().enumerate() {
+            standing.rank = rank as u32 + 1;
+        }
+        standings
+    }
+    
+    /// Pair the next round, or finish the league after the last one
+    fn start_round(&mut self) -> Vec<usize> {
+        if self.current_round >= self.rounds {
+            self.status = LeagueStatus::Finished;
+            return Vec::new();
+        }
+        self.current_round += 1;
+        
+        let pairs = match self.format {
+            LeagueFormat::Swiss => self.swiss_pairs(),
+            LeagueFormat::RoundRobin => self.round_robin_pairs(),
+        };
+        let mut games = Vec::new();
+        for (player1, player2) in pairs {
+            if player2.is_some() {
+                games.push(self.pairings.len());
+            }
+            self.pairings.push(LeaguePairing {
+                round: self.current_round,
+                player1,
+                player2,
+                room_id: None,
+                winner: player2.map_or(Some(player1), |_| None),
+                finished: player2.is_none(),
+            });
+        }
+        games
+    }
+    
+    fn have_played(&self, a: ChainId, b: ChainId) -> bool {
+        self.pairings.iter().any(|pairing| pairing.opponent(a) == Some(b))
+    }
+    
+    /// Swiss pairings: players are ranked by points and paired from the top, each with the
+    /// highest ranked player they have not met yet, so players mostly meet their own score
+    /// group. With an odd field the lowest ranked player without a bye sits out.
+    fn swiss_pairs(&self) -> Vec<(ChainId, Option<ChainId>)> {
+        let mut ranked: Vec<(ChainId, f64)> = self.compute_standings().into_iter()
+            .map(|standing| (standing.player, standing.points))
+            .collect();
+        // Within a score group keep seed order rather than tiebreak order
+        let seed = |player: &ChainId| self.players.iter().position(|p| p == player);
+        ranked.sort_by(|a, b| b.1.total_cmp(&a.1).then_with(|| seed(&a.0).cmp(&seed(&b.0))));
+        let mut order: Vec<ChainId> = ranked.into_iter().map(|(player, _)| player).collect();
+        
+        let mut pairs = Vec::new();
+        if order.len() % 2 == 1 {
+            let had_bye = |player: &ChainId| self.pairings.iter().any(|pairing| pairing.is_bye() && pairing.player1 == *player);
+            let index = order.iter().rposition(|player| !had_bye(player)).unwrap_or(order.len() - 1);
+            pairs.push((order.remove(index), None));
+        }
+        
+        let mut budget = SWISS_SEARCH_BUDGET;
+        let games = self.pair_unplayed(&order, &mut budget)
+            // Every pairing left repeats an earlier game: pair neighbours instead
+            .unwrap_or_else(|| order.chunks(2).map(|pair| (pair[0], pair[1])).collect());
+        pairs.extend(games.into_iter().map(|(player1, player2)| (player1, Some(player2))));
+        pairs
+    }
+    
+    /// Pair `players` in order without repeating an earlier game, backtracking when a choice
+    /// leaves the rest unpairable
+    fn pair_unplayed(&self, players: &[ChainId], budget: &mut u32) -> Option<Vec<(ChainId, ChainId)>> {
+        let Some((&first, rest)) = players.split_first() else {
+            return Some(Vec::new());
+        };
+        for (index, &opponent) in rest.iter().enumerate() {
+            if *budget == 0 {
+                return None;
+            }
+            *budget -= 1;
+            if self.have_played(first, opponent) {
+                continue;
+            }
+            let mut others = rest.to_vec();
+            others.remove(index);
+            if let Some(mut pairs) = self.pair_unplayed(&others, budget) {
+                pairs.insert(0, (first, opponent));
+                return Some(pairs);
+            }
+        }
+        None
+    }
+    
+    /// Round-robin pairings using the circle method: the first player stays in place while
+    /// the others rotate one seat per round. An odd field adds an empty seat for byes.
+    fn round_robin_pairs(&self) -> Vec<(ChainId, Option<ChainId>)> {
+        let mut seats: Vec<Option<ChainId>> = self.players.iter().copied().map(Some).collect();
+        if seats.len() % 2 == 1 {
+            seats.push(None);
+        }
+        let count = seats.len();
+        let shift = (self.current_round as usize - 1) % (count - 1);
+        seats[1..].rotate_right(shift);
+        
+        (0..count / 2)
+            .filter_map(|index| {
+                let (mut home, mut away) = (seats[index], seats[count - 1 - index]);
+                // Alternate seats for the fixed player
+                if index == 0 && self.current_round.is_multiple_of(2) {
+                    std::mem::swap(&mut home, &mut away);
+                }
+                match (home, away) {
+                    (Some(home), away) => Some((home, away)),
+                    (None, Some(away)) => Some((away, None)),
+                    (None, None) => None,
+                }
+            })
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_utils::{chain, chains};
+    
+    fn league(players: u8, format: LeagueFormat, rounds: Option<u32>) -> League {
+        let mut league = League::new(
+            "L1".to_string(),
+            "Test".to_string(),
+            format,
+            rounds,
+            MAX_LEAGUE_PLAYERS as u32,
+            false,
+            MatchFormat::default(),
+            60,
+            TimeoutPenalty::default(),
+            0,
+        );
+        league.players = chains(players);
+        league
+    }
+    
+    /// Play the whole league, with `pick` deciding each game, `None` for a draw
+    fn play(league: &mut League, pick: impl Fn(&LeaguePairing) -> Option<ChainId>) {
+        let mut games = league.start(league.players.clone()).unwrap();
+        while !games.is_empty() {
+            let mut next = Vec::new();
+            for index in games {
+                let winner = pick(&league.pairings[index]);
+                next.extend(league.record_result(index, winner).unwrap());
+            }
+            games = next;
+        }
+        assert_eq!(league.status, LeagueStatus::Finished);
+    }
+    
+    /// Lower chain ID wins, so seed order is also strength order
+    fn favourite(pairing: &LeaguePairing) -> Option<ChainId> {
+        pairing.player2.map(|player2| pairing.player1.min(player2))
+    }
+    
+    fn meetings(league: &League, a: ChainId, b: ChainId) -> usize {
+        league.pairings.iter().filter(|pairing| pairing.opponent(a) == Some(b)).count()
+    }
+    
+    fn byes(league: &League, player: ChainId) -> usize {
+        league.pairings.iter().filter(|pairing| pairing.is_bye() && pairing.player1 == player).count()
+    }
+    
+    fn finished(round: u32, player1: ChainId, player2: Option<ChainId>, winner: Option<ChainId>) -> LeaguePairing {
+        LeaguePairing {
+            round,
+            player1,
+            player2,
+            room_id: None,
+            winner,
+            finished: true,
+        }
+    }
+    
+    #[test]
+    fn round_robin_meets_everyone_once() {
+        for players in 2..=9u8 {
+            let mut league = league(players, LeagueFormat::RoundRobin, None);
+            play(&mut league, |pairing| if pairing.round % 3 == 0 { None } else { favourite(pairing) });
+            
+            assert_eq!(league.rounds, u32::from(players + players % 2 - 1));
+            for a in 1..=players {
+                for b in (a + 1)..=players {
+                    assert_eq!(meetings(&league, chain(a), chain(b)), 1, "{} players: {} v {}", players, a, b);
+                }
+                assert_eq!(byes(&league, chain(a)), usize::from(players % 2));
+            }
+            
+            // Every round pairs every player exactly once
+            for round in 1..=league.rounds {
+                let mut seated: Vec<ChainId> = league.pairings.iter()
+                    .filter(|pairing| pairing.round == round)
+                    .flat_map(|pairing| [Some(pairing.player1), pairing.player2])
+                    .flatten()
+                    .collect();
+                seated.sort();
+                seated.dedup();
+                assert_eq!(seated.len(), usize::from(players));
+            }
+        }
+    }
+    
+    #[test]
+    fn swiss_never_repeats_a_pairing() {
+        for players in 2..=16u8 {
+            let mut league = league(players, LeagueFormat::Swiss, None);
+            play(&mut league, favourite);
+            
+            assert_eq!(league.rounds, u32::from(players).next_power_of_two().trailing_zeros());
+            for a in 1..=players {
+                for b in (a + 1)..=players {
+                    assert!(meetings(&league, chain(a), chain(b)) <= 1, "{} players: {} v {}", players, a, b);
+                }
+            }
+        }
+    }
+    
+    #[test]
+    fn swiss_byes_rotate() {
+        for players in [3u8, 5, 7, 9] {
+            let mut league = league(players, LeagueFormat::Swiss, Some(u32::from(players)));
+            play(&mut league, favourite);
+            
+            // As many rounds as players: everyone sits out exactly once
+            for player in chains(players) {
+                assert_eq!(byes(&league, player), 1, "{} players", players);
+            }
+            
+            // The first bye goes to the lowest seed
+            let first_bye = league.pairings.iter().find(|pairing| pairing.is_bye()).unwrap();
+            assert_eq!(first_bye.player1, chain(players));
+        }
+    }
+    
+    #[test]
+    fn swiss_pairing_backtracks() {
+        let mut league = league(4, LeagueFormat::Swiss, None);
+        let (a, b, c, d) = (chain(1), chain(2), chain(3), chain(4));
+        league.pairings = vec![finished(1, a, Some(b), None), finished(2, b, Some(d), None)];
+        
+        // Pairing a with c first would leave b and d, who already met
+        let mut budget = SWISS_SEARCH_BUDGET;
+        assert_eq!(league.pair_unplayed(&[a, b, c, d], &mut budget), Some(vec![(a, d), (b, c)]));
+    }
+    
+    #[test]
+    fn swiss_falls_back_to_repeat_pairings() {
+        let mut league = league(2, LeagueFormat::Swiss, None);
+        let (a, b) = (chain(1), chain(2));
+        league.pairings = vec![finished(1, a, Some(b), Some(a))];
+        
+        let mut budget = SWISS_SEARCH_BUDGET;
+        assert_eq!(league.pair_unplayed(&[a, b], &mut budget), None);
+        assert_eq!(league.swiss_pairs(), vec![(a, Some(b))]);
+        
+        // Running out of budget also falls back instead of searching forever
+        let mut budget = 0;
+        assert_eq!(league.pair_unplayed(&[a, b], &mut budget), None);
+    }
+    
+    #[test]
+    fn standings_break_ties_by_buchholz() {
+        let mut league = league(5, LeagueFormat::Swiss, None);
+        let (a, b, c, d, e) = (chain(1), chain(2), chain(3), chain(4), chain(5));
+        league.pairings = vec![
+            finished(1, a, Some(b), Some(a)),
+            finished(1, c, Some(d), Some(c)),
+            finished(1, e, None, Some(e)),
+            finished(2, d, Some(a), Some(d)),
+            finished(2, c, Some(e), None),
+            finished(2, b, None, Some(b)),
+        ];
+        
+        let standings = league.compute_standings();
+        let table: Vec<(ChainId, f64, f64)> = standings.iter()
+            .map(|standing| (standing.player, standing.points, standing.buchholz))
+            .collect();
+        // c: win and draw; e: bye and draw; d and a tie on one point, but d met stronger
+        // opponents; b's bye earns a point without adding to anyone's Buchholz
+        assert_eq!(table, vec![
+            (c, 1.5, 2.5),
+            (e, 1.5, 1.5),
+            (d, 1.0, 2.5),
+            (a, 1.0, 2.0),
+            (b, 1.0, 1.0),
+        ]);
+        assert_eq!(standings.iter().map(|standing| standing.rank).collect::<Vec<_>>(), vec![1, 2, 3, 4, 5]);
+        assert_eq!((standings[0].wins, standings[0].draws, standings[0].losses), (1, 1, 0));
+        assert_eq!((standings[1].byes, standings[1].draws), (1, 1));
+    }
+}
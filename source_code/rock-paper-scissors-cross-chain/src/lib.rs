@@ -3,15 +3,66 @@
 
 /*! ABI of the Rock Paper Scissors Cross-Chain Application */
 
+use std::cmp::Ordering;
+use std::fmt;
+
 use async_graphql::{Request, Response};
-use linera_sdk::linera_base_types::{ChainId, ContractAbi, ServiceAbi};
+use linera_sdk::abis::fungible::FungibleTokenAbi;
+use linera_sdk::linera_base_types::{AccountOwner, Amount, ApplicationId, ChainId, ContractAbi, ServiceAbi};
 use serde::{Deserialize, Serialize};
+use sha3::{Digest, Sha3_256};
+
+pub mod glicko2;
+pub mod league;
+pub mod rewards;
+pub mod tournament;
+
+#[cfg(test)]
+mod test_utils;
+
+use glicko2::Glicko2Config;
+use league::{League, LeagueFormat};
+use rewards::RewardConfig;
+use tournament::{SeedingMethod, Tournament, TournamentFormat};
+
+/// Maximum length of a player display name, in characters
+pub const MAX_PLAYER_NAME_LEN: usize = 32;
+
+/// Maximum length of a room alias, in characters
+pub const MAX_ROOM_ALIAS_LEN: usize = 32;
+
+/// Crockford base32 alphabet used for room codes (no I, L, O or U)
+const ROOM_CODE_ALPHABET: &[u8; 32] = b"0123456789ABCDEFGHJKMNPQRSTVWXYZ";
+
+/// Minimum number of digits in a room code, before the checksum
+const ROOM_CODE_MIN_DIGITS: usize = 4;
+
+/// Length of a choice commitment: a SHA3-256 digest in hex
+const COMMITMENT_LEN: usize = 64;
+
+/// Elo rating given to players before their first game
+pub const DEFAULT_ELO_RATING: i32 = 1200;
+
+/// Number of players shown on the global leaderboard
+pub const LEADERBOARD_SIZE: usize = 100;
+
+/// Default time a player has to act in each round phase before the opponent can claim a timeout
+pub const DEFAULT_MOVE_TIMEOUT_SECS: u64 = 120;
+
+/// Rating difference a newly queued player accepts for a quick match
+pub const MATCHMAKING_BASE_BAND: i32 = 100;
+/// How much the accepted rating difference widens per step of waiting
+pub const MATCHMAKING_BAND_STEP: i32 = 50;
+/// Seconds of waiting per widening step
+pub const MATCHMAKING_BAND_STEP_SECS: u64 = 10;
+/// Largest rating difference ever accepted for a quick match
+pub const MATCHMAKING_MAX_BAND: i32 = 1000;
 
 pub struct RockPaperScissorsAbi;
 
 impl ContractAbi for RockPaperScissorsAbi {
     type Operation = Operation;
-    type Response = ();
+    type Response = OperationResponse;
 }
 
 impl ServiceAbi for RockPaperScissorsAbi {
@@ -27,6 +78,14 @@ pub enum Choice {
     Scissors,
 }
 
+// What entry fees and prizes are paid in
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq, Default, async_graphql::Enum)]
+pub enum Currency {
+    #[default]
+    Native, // Native Linera tokens, moved with runtime transfers
+    Token,  // The fungible token application set in the application parameters
+}
+
 // Game result for a single round
 #[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq, async_graphql::Enum)]
 pub enum RoundResult {
@@ -35,41 +94,246 @@ pub enum RoundResult {
     Draw,
 }
 
+// Lifecycle of a game room
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq, async_graphql::Enum)]
+pub enum RoomStatus {
+    WaitingForOpponent, // Open seat, listed in available rooms when public
+    InProgress,         // Both players seated, collecting commitments for the current round
+    AwaitingReveal,     // Both players committed, collecting reveals for the current round
+    Finished,           // Game decided, see `GameResult`
+    Abandoned,          // Nobody acted before the deadline
+    Cancelled,          // Closed before the match was played
+}
+
+// What a player forfeits when they miss a move deadline
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq, Default, async_graphql::Enum)]
+pub enum TimeoutPenalty {
+    #[default]
+    ForfeitRound,
+    ForfeitGame,
+}
+
+// How a game is decided once the round or draw cap is reached
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq, Default, async_graphql::Enum)]
+pub enum TiebreakRule {
+    #[default]
+    SuddenDeath, // The next decisive round wins the game
+    MostWins,    // The player with more round wins takes the game, sudden death if level
+}
+
+// Match format of a room: first to `wins_to_clinch`, optionally capped in rounds or draws
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq, async_graphql::SimpleObject, async_graphql::InputObject)]
+#[graphql(input_name = "MatchFormatInput")]
+pub struct MatchFormat {
+    pub wins_to_clinch: u8, // Round wins needed to win the game
+    pub max_rounds: Option<u16>, // Round cap, draws included
+    pub max_draws: Option<u16>, // Draw cap
+    pub tiebreak: TiebreakRule, // Applied once a cap is reached
+}
+
 // Round history entry
 #[derive(Debug, Clone, Serialize, Deserialize, async_graphql::SimpleObject)]
 pub struct RoundHistory {
-    pub round_number: u8,
-    pub player1_choice: Choice,
-    pub player2_choice: Choice,
+    pub round_number: u16,
+    pub player1_choice: Option<Choice>, // None if player 1 forfeited the round
+    pub player2_choice: Option<Choice>, // None if player 2 forfeited the round
     pub result: RoundResult,
     pub winner: Option<ChainId>,
+    pub forfeited_by: Option<ChainId>, // Player who missed the move deadline
 }
 
-// Overall game result (best of 5)
+// Overall game result, decided according to the room's `MatchFormat`
 #[derive(Debug, Clone, Serialize, Deserialize, async_graphql::SimpleObject)]
 pub struct GameResult {
     pub player1_wins: u8,
     pub player2_wins: u8,
-    pub draws: u8,
+    pub draws: u16,
     pub winner: Option<ChainId>, // None if game not finished
     pub is_finished: bool,
+    pub resigned_by: Option<ChainId>, // Set if the game ended by resignation
 }
 
 // Game room structure
 #[derive(Debug, Clone, Serialize, Deserialize, async_graphql::SimpleObject)]
+#[graphql(complex)]
 pub struct GameRoom {
-    pub room_id: String,
+    pub room_id: String, // Room code allocated by the leaderboard chain
+    pub alias: Option<String>, // Optional vanity name chosen by the creator
+    pub host: Option<ChainId>, // Player chain that requested the room, None for rooms created on the leaderboard chain
     pub player1: Option<ChainId>,
     pub player2: Option<ChainId>,
     pub player1_name: Option<String>,
     pub player2_name: Option<String>,
-    pub player1_choice: Option<Choice>,
-    pub player2_choice: Option<Choice>,
+    pub player1_commitment: Option<String>, // Hash of player 1's choice for the current round
+    pub player2_commitment: Option<String>, // Hash of player 2's choice for the current round
+    pub player1_choice: Option<Choice>, // Only set once revealed
+    pub player2_choice: Option<Choice>, // Only set once revealed
+    pub status: RoomStatus,
     pub game_result: GameResult,
     pub created_at: u64,
-    pub round_number: u8, // Current round (1-5)
+    pub round_number: u16, // Current round, starting at 1
+    pub format: MatchFormat, // How the game is decided
     pub private: bool, // Whether the room is private or public
     pub round_history: Vec<RoundHistory>, // History of completed rounds
+    pub move_timeout_secs: u64, // Time each player has to act in a round phase
+    pub timeout_penalty: TimeoutPenalty, // What a player loses when missing the deadline
+    pub phase_deadline: Option<u64>, // Deadline (micros) for the current round phase
+    pub allowed_players: Vec<ChainId>, // Chains invited to a private room
+    #[graphql(skip)]
+    pub invite_code_hash: Option<String>, // Hash of the invite code a joiner must present
+    pub rematch_offered_by: Option<ChainId>, // Player who offered a rematch after the game
+    pub previous_room: Option<String>, // Room this one is a rematch of, or the previous game of its series
+    pub next_room: Option<String>, // Rematch room, or the next game of its series
+    pub series_id: Option<String>, // Series this room is a game of
+    pub tournament_id: Option<String>, // Tournament this room is a bracket match of
+    pub league_id: Option<String>, // League this room is a pairing of
+    pub entry_fee: Amount, // Tokens each player pays into escrow to take a seat
+    pub currency: Currency, // What the entry fee and the pot are paid in
+    pub pot: Amount, // Entry fees held in escrow, paid to the winner when the game finishes
+    #[graphql(skip)]
+    pub stakes: Vec<Stake>, // Entry fees not yet paid out or refunded
+}
+
+// Entry fee a player transferred to the application account on the leaderboard chain
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq)]
+pub struct Stake {
+    pub player: ChainId,
+    pub owner: AccountOwner, // Account the fee was paid from, and refunds or prizes go to
+    pub amount: Amount,
+    pub currency: Currency,
+}
+
+// Best-of-N series of games between the same two players, run by the leaderboard chain
+#[derive(Debug, Clone, Serialize, Deserialize, async_graphql::SimpleObject)]
+pub struct Series {
+    pub series_id: String,
+    pub player1: ChainId,
+    pub player2: ChainId,
+    pub player1_name: Option<String>,
+    pub player2_name: Option<String>,
+    pub best_of: u8, // Number of games, odd
+    pub player1_wins: u8, // Games won by player 1
+    pub player2_wins: u8, // Games won by player 2
+    pub rooms: Vec<String>, // Rooms of the games played so far, in order
+    pub private: bool, // Games are private rooms and the series is not recorded on the leaderboard
+    pub format: MatchFormat, // Format of each game
+    pub move_timeout_secs: u64,
+    pub timeout_penalty: TimeoutPenalty,
+    pub winner: Option<ChainId>, // Set once a player clinches the series
+    pub created_at: u64,
+}
+
+// Who may join a private room besides the host
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct RoomAccess {
+    pub allowed_players: Vec<ChainId>, // Chains admitted without an invite code
+    pub invite_code_hash: Option<String>, // See `invite_code_hash`
+}
+
+impl RoomAccess {
+    /// Returns true if anyone may join
+    pub fn is_open(&self) -> bool {
+        self.allowed_players.is_empty() && self.invite_code_hash.is_none()
+    }
+}
+
+// Error codes returned by operations and reported back for rejected cross-chain requests
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq, async_graphql::Enum)]
+pub enum GameError {
+    LeaderboardNotConfigured,
+    AlreadyConfigured,
+    NotLeaderboardChain,
+    InvalidName,
+    InvalidRoomAlias,
+    InvalidMatchFormat,
+    RoomNotFound,
+    RoomAlreadyExists,
+    RoomFull,
+    AlreadyInRoom,
+    NotAPlayer,
+    InvalidRoomState,
+    AlreadyCommitted,
+    AlreadyRevealed,
+    CommitmentMismatch,
+    InvalidCommitment,
+    NoTimeoutToClaim,
+    AlreadyQueued,
+    NotQueued,
+    NotInvited,
+    InvalidInviteCode,
+    InviteRequiresPrivateRoom,
+    NotHost,
+    CannotKickHost,
+    MatchAlreadyStarted,
+    NoRematchOffered,
+    RematchAlreadyStarted,
+    WageredRematch,
+    InvalidSeries,
+    InvalidTournament,
+    TournamentNotFound,
+    TournamentNotOpen,
+    AlreadyRegistered,
+    TournamentFull,
+    NotEnoughPlayers,
+    InvalidLeague,
+    LeagueNotFound,
+    LeagueNotOpen,
+    LeagueFull,
+    WrongEntryFee,
+    EntryFeeRequiresSigner,
+    TokenNotConfigured,
+    StorageError,
+}
+
+// Successful operation payload
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[allow(clippy::large_enum_variant)]
+pub enum OperationOutcome {
+    Done,                            // Applied on this chain
+    SentToLeaderboard,               // Forwarded to the leaderboard chain, see `RejectedAction` for failures
+    Room(GameRoom),                  // Created or requested room
+    Rooms(Vec<GameRoom>),            // Available rooms
+    Leaderboard(Vec<LeaderboardEntry>),
+    Series(Series),                  // Created series
+    Tournament(Tournament),          // Created or started tournament
+    League(League),                  // Created or started league
+}
+
+pub type OperationResponse = Result<OperationOutcome, GameError>;
+
+// Request rejected by the leaderboard chain, reported back to the requesting chain
+#[derive(Debug, Clone, Serialize, Deserialize, async_graphql::SimpleObject)]
+pub struct RejectedAction {
+    pub room_id: Option<String>,
+    pub error: GameError,
+    pub timestamp: u64,
+}
+
+// Player waiting in the matchmaking queue on the leaderboard chain
+#[derive(Debug, Clone, Serialize, Deserialize, async_graphql::SimpleObject)]
+pub struct QueueEntry {
+    pub player_chain: ChainId,
+    pub player_name: Option<String>,
+    pub rating: i32, // Elo rating when the player queued
+    pub queued_at: u64,
+}
+
+impl QueueEntry {
+    /// Rating difference this player accepts at `now`, widening the longer they wait
+    pub fn band(&self, now: u64) -> i32 {
+        let waited_secs = now.saturating_sub(self.queued_at) / 1_000_000;
+        let steps = (waited_secs / MATCHMAKING_BAND_STEP_SECS).min(i32::MAX as u64) as i32;
+        MATCHMAKING_BASE_BAND
+            .saturating_add(steps.saturating_mul(MATCHMAKING_BAND_STEP))
+            .min(MATCHMAKING_MAX_BAND)
+    }
+    
+    /// Whether two queued players can be paired at `now`; the longer waiting player's
+    /// band decides
+    pub fn accepts(&self, other: &QueueEntry, now: u64) -> bool {
+        let band = self.band(now).max(other.band(now));
+        (self.rating - other.rating).abs() <= band
+    }
 }
 
 // Leaderboard entry for global statistics
@@ -80,40 +344,211 @@ pub struct LeaderboardEntry {
     pub wins: u64,
     pub losses: u64,
     pub total_games: u64,
+    pub rating: i32, // Elo rating
+    pub provisional: bool, // Rating still settling during the first games
+    pub series_played: u64, // Best-of-N series, counted separately from games
+    pub series_won: u64,
+}
+
+// Ordering of the global leaderboard
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq, Default, async_graphql::Enum)]
+pub enum LeaderboardOrder {
+    #[default]
+    Wins, // Wins, then win rate, then total games
+    Rating, // Elo rating, then wins
+}
+
+// Ordering of room search results
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq, Default, async_graphql::Enum)]
+pub enum RoomOrder {
+    #[default]
+    Newest, // Most recently created first
+    Oldest, // Earliest created first
+    RoomId, // Room code, alphabetically
+}
+
+// Criteria a room must meet to be listed by a room search; unset fields match every room
+#[derive(Debug, Clone, Default, Serialize, Deserialize, async_graphql::InputObject)]
+pub struct RoomFilter {
+    pub status: Option<Vec<RoomStatus>>, // Any of these statuses
+    pub private: Option<bool>,
+    pub player: Option<ChainId>, // Seated in the room
+    pub created_after: Option<u64>, // Inclusive, in micros
+    pub created_before: Option<u64>, // Exclusive, in micros
+    pub wins_to_clinch: Option<u8>,
+    pub format: Option<MatchFormat>, // Exact match format
+    pub room_id_prefix: Option<String>, // Start of the room code or alias, in any case
+}
+
+// Elo rating configuration for the global leaderboard
+#[derive(Debug, Clone, Copy, Deserialize, Serialize)]
+#[serde(default)]
+pub struct EloConfig {
+    pub initial_rating: i32,
+    pub k_factor: u32,
+    pub provisional_k_factor: u32, // K-factor used while a player is provisional
+    pub provisional_games: u64, // Number of games a player stays provisional
 }
 
 // Application parameters for leaderboard configuration
 #[derive(Debug, Clone, Deserialize, Serialize, Default)]
 pub struct ApplicationParameters {
     pub leaderboard_chain_id: Option<ChainId>,
+    #[serde(default)]
+    pub elo: EloConfig,
+    #[serde(default)]
+    pub glicko2: Glicko2Config,
+    #[serde(default)]
+    pub rewards: RewardConfig,
+    #[serde(default)]
+    pub fungible_token: Option<ApplicationId<FungibleTokenAbi>>, // Token rooms can charge entry fees in
 }
 
 // Cross-chain messages
 #[derive(Debug, Clone, Deserialize, Serialize)]
 pub enum GameMessage {
+    // Request to create a room on leaderboard chain, hosted by the requesting player
+    CreateRoomRequest {
+        alias: Option<String>,
+        host: ChainId,
+        host_name: Option<String>,
+        private: bool,
+        access: RoomAccess,
+        format: Option<MatchFormat>,
+        move_timeout_secs: Option<u64>,
+        timeout_penalty: Option<TimeoutPenalty>,
+        entry_fee: Amount,
+        currency: Currency,
+        stake: Option<Stake>, // The host's entry fee, already transferred to escrow
+    },
+    // Confirmation that the requested room was created with the host as player 1
+    RoomCreated {
+        room_id: String,
+        alias: Option<String>,
+        host: ChainId,
+    },
     // Request to join a room on leaderboard chain
     JoinRoom {
         room_id: String,
         player_chain: ChainId,
         player_name: Option<String>,
+        invite_code: Option<String>, // In clear text, checked against the room's invite code hash
+        stake: Option<Stake>, // Entry fee already transferred to escrow
     },
-    // Confirmation that player joined room
+    // Confirmation that player joined room, or why they could not
     PlayerJoined {
         room_id: String,
         player_chain: ChainId,
-        success: bool,
+        error: Option<GameError>,
     },
-    // Submit a choice for current round
-    SubmitChoice {
+    // A request to the leaderboard chain was rejected
+    ActionRejected {
+        room_id: Option<String>,
+        error: GameError,
+    },
+    // Commit to a hashed choice for current round
+    CommitChoice {
+        room_id: String,
+        player_chain: ChainId,
+        commitment: String,
+    },
+    // Both players committed, choices can now be revealed
+    RevealPhaseStarted {
+        room_id: String,
+        round_number: u16,
+    },
+    // Reveal the committed choice for current round
+    RevealChoice {
         room_id: String,
         player_chain: ChainId,
         choice: Choice,
+        salt: String,
+    },
+    // Claim the current round (or game) because the opponent missed the deadline
+    ClaimTimeout {
+        room_id: String,
+        claimant: ChainId,
+    },
+    // Room closed without a winner (abandoned or cancelled)
+    RoomClosed {
+        room_id: String,
+        status: RoomStatus,
+    },
+    // Leave a room before the match starts
+    LeaveRoom {
+        room_id: String,
+        player_chain: ChainId,
+    },
+    // Concede an active match to the opponent
+    Resign {
+        room_id: String,
+        player_chain: ChainId,
+    },
+    // Offer the opponent a rematch of a finished room
+    OfferRematch {
+        room_id: String,
+        player_chain: ChainId,
+    },
+    // Accept the opponent's rematch offer
+    AcceptRematch {
+        room_id: String,
+        player_chain: ChainId,
+    },
+    // The opponent offered a rematch, sent to the other player
+    RematchOffered {
+        room_id: String,
+        offered_by: ChainId,
+    },
+    // Series finished notification
+    SeriesFinished {
+        series_id: String,
+        winner: ChainId,
+        player1_wins: u8,
+        player2_wins: u8,
+    },
+    // Sent from player chain to leaderboard chain to enter a tournament
+    RegisterForTournament {
+        tournament_id: String,
+        player_chain: ChainId,
+        stake: Option<Stake>, // Entry fee already transferred to escrow
+    },
+    // Tournament finished notification, sent to every registered player
+    TournamentFinished {
+        tournament_id: String,
+        champion: Option<ChainId>,
+    },
+    // Sent from player chain to leaderboard chain to enter a league
+    RegisterForLeague {
+        league_id: String,
+        player_chain: ChainId,
+    },
+    // League finished notification, sent to every registered player
+    LeagueFinished {
+        league_id: String,
+        winner: Option<ChainId>,
+    },
+    // Host cancels a room before both players have committed their first choice
+    CancelRoom {
+        room_id: String,
+        requester: ChainId,
+    },
+    // Host removes a player before any choice was made
+    KickPlayer {
+        room_id: String,
+        requester: ChainId,
+        player: ChainId,
+    },
+    // A seat was freed, sent to the removed player and whoever is still seated
+    SeatVacated {
+        room_id: String,
+        player_chain: ChainId,
+        kicked: bool,
     },
     // Round completed notification
     RoundCompleted {
         room_id: String,
-        player1_choice: Choice,
-        player2_choice: Choice,
+        player1_choice: Option<Choice>,
+        player2_choice: Option<Choice>,
         round_winner: Option<ChainId>,
         round_result: RoundResult,
         game_result: GameResult,
@@ -134,6 +569,15 @@ pub enum GameMessage {
         player_chain: ChainId,
         player_name: String,
     },
+    // Join the matchmaking queue, answered with PlayerJoined once paired
+    QueueForMatch {
+        player_chain: ChainId,
+        player_name: Option<String>,
+    },
+    // Leave the matchmaking queue
+    LeaveQueue {
+        player_chain: ChainId,
+    },
 }
 
 #[derive(Debug, Serialize, Deserialize)]
@@ -148,20 +592,125 @@ pub enum Operation {
         name: String,
     },
     
-    // Game operations (only allowed on leaderboard chain)
+    // Game operations (forwarded to the leaderboard chain from player chains, with the player as host)
     CreateRoom {
-        room_id: String,
+        alias: Option<String>, // The room code is allocated by the leaderboard chain
+        private: bool,
+        allowed_players: Vec<ChainId>, // Private rooms only: chains that may join
+        invite_code: Option<String>, // Private rooms only: code a joiner must present, only its hash leaves this chain
+        format: Option<MatchFormat>,
+        move_timeout_secs: Option<u64>,
+        timeout_penalty: Option<TimeoutPenalty>,
+        entry_fee: Option<Amount>, // Paid by every player, the host included, when taking a seat
+        currency: Option<Currency>, // Defaults to native tokens
+    },
+    
+    // Best-of-N series between two players, each game in its own room (only on leaderboard chain)
+    CreateSeries {
+        player1: ChainId,
+        player2: ChainId,
+        best_of: u8,
+        private: bool,
+        format: Option<MatchFormat>,
+        move_timeout_secs: Option<u64>,
+        timeout_penalty: Option<TimeoutPenalty>,
+    },
+    
+    // Elimination tournament, players register until it is started (only on leaderboard chain)
+    CreateTournament {
+        name: String,
+        format: TournamentFormat,
+        seeding: SeedingMethod,
+        max_players: Option<u32>,
+        players: Vec<ChainId>, // Registered right away, without paying the entry fee
         private: bool,
+        match_format: Option<MatchFormat>,
+        move_timeout_secs: Option<u64>,
+        timeout_penalty: Option<TimeoutPenalty>,
+        entry_fee: Option<Amount>, // Paid on registration into the prize pool
+        currency: Option<Currency>, // Defaults to native tokens
+    },
+    // Close registration, seed the bracket and open the first matches (only on leaderboard chain)
+    StartTournament {
+        tournament_id: String,
+    },
+    // Close a tournament still in registration and refund its entry fees (only on leaderboard chain)
+    CancelTournament {
+        tournament_id: String,
+    },
+    // Swiss or round-robin league played in rounds (only on leaderboard chain)
+    CreateLeague {
+        name: String,
+        format: LeagueFormat,
+        rounds: Option<u32>, // Swiss only, defaults to log2 of the field
+        max_players: Option<u32>,
+        players: Vec<ChainId>, // Registered right away
+        private: bool,
+        match_format: Option<MatchFormat>,
+        move_timeout_secs: Option<u64>,
+        timeout_penalty: Option<TimeoutPenalty>,
+    },
+    // Close registration and pair the first round (only on leaderboard chain)
+    StartLeague {
+        league_id: String,
     },
     
     // Player operations (allowed on any chain)
+    RegisterForTournament {
+        tournament_id: String,
+        entry_fee: Option<Amount>, // Must match the tournament's entry fee
+        currency: Option<Currency>,
+    },
+    RegisterForLeague {
+        league_id: String,
+    },
     JoinRoom {
         room_id: String,
+        invite_code: Option<String>, // Sent to the leaderboard chain in clear text
+        entry_fee: Option<Amount>, // Must match the room's entry fee, paid from the signer's account
+        currency: Option<Currency>,
+    },
+    CommitChoice {
+        room_id: String,
+        commitment: String,
     },
-    SubmitChoice {
+    RevealChoice {
         room_id: String,
         choice: Choice,
+        salt: String,
     },
+    // Permissionless: awards the round to the player still waiting on an opponent past the deadline
+    ClaimTimeout {
+        room_id: String,
+    },
+    // Leave a room before any choice was made; a leaving host cancels the room
+    LeaveRoom {
+        room_id: String,
+    },
+    // Concede an active match; the opponent wins the game
+    Resign {
+        room_id: String,
+    },
+    // After the game: offer the opponent a rematch with sides swapped
+    OfferRematch {
+        room_id: String,
+    },
+    AcceptRematch {
+        room_id: String,
+    },
+    // Host only: close the room before both players have committed their first choice; series,
+    // tournament and league games cannot be cancelled
+    CancelRoom {
+        room_id: String,
+    },
+    // Host only: remove a player before any choice was made
+    KickPlayer {
+        room_id: String,
+        player: ChainId,
+    },
+    // Wait for an opponent with a similar rating; a room is created once one is found
+    QuickMatch,
+    LeaveQueue,
     
     // Query operations
     GetAvailableRooms,
@@ -198,27 +747,440 @@ impl Choice {
     }
 }
 
+impl GameError {
+    /// Human readable description of the error
+    pub fn message(&self) -> &'static str {
+        match self {
+            GameError::LeaderboardNotConfigured => "No leaderboard chain configured, use SetupLeaderboard first",
+            GameError::AlreadyConfigured => "Leaderboard already configured",
+            GameError::NotLeaderboardChain => "Operation can only be performed on the leaderboard chain",
+            GameError::InvalidName => "Player name must be 1 to 32 printable characters",
+            GameError::InvalidRoomAlias => "Room alias must be 1 to 32 letters, digits, '-' or '_'",
+            GameError::InvalidMatchFormat => "Match format needs at least one win to clinch and caps that allow it",
+            GameError::RoomNotFound => "Room not found",
+            GameError::RoomAlreadyExists => "A room with this ID already exists",
+            GameError::RoomFull => "Room is full",
+            GameError::AlreadyInRoom => "Player already joined this room",
+            GameError::NotAPlayer => "Player is not part of this room",
+            GameError::InvalidRoomState => "Action not allowed in the current room status",
+            GameError::AlreadyCommitted => "A choice was already committed for this round",
+            GameError::AlreadyRevealed => "The choice was already revealed for this round",
+            GameError::CommitmentMismatch => "Revealed choice does not match the commitment",
+            GameError::InvalidCommitment => "Commitment must be a hex-encoded SHA3-256 digest",
+            GameError::NoTimeoutToClaim => "No player has missed the move deadline",
+            GameError::AlreadyQueued => "Player is already waiting in the matchmaking queue",
+            GameError::NotQueued => "Player is not in the matchmaking queue",
+            GameError::NotInvited => "Room is invite-only and this player is not invited",
+            GameError::InvalidInviteCode => "Invite code does not match",
+            GameError::InviteRequiresPrivateRoom => "Allow-lists and invite codes are only available for private rooms",
+            GameError::NotHost => "Only the room host can do this",
+            GameError::CannotKickHost => "The host cannot be kicked, cancel the room instead",
+            GameError::MatchAlreadyStarted => "Too late, the match has already started",
+            GameError::NoRematchOffered => "The opponent has not offered a rematch",
+            GameError::RematchAlreadyStarted => "A rematch of this room was already created",
+            GameError::WageredRematch => "Rooms with an entry fee cannot be rematched",
+            GameError::InvalidSeries => "A series needs two different players and an odd number of games",
+            GameError::InvalidTournament => "A tournament needs a name and room for 2 to 256 players",
+            GameError::TournamentNotFound => "Tournament not found",
+            GameError::TournamentNotOpen => "Registration for this tournament is closed",
+            GameError::AlreadyRegistered => "Already registered",
+            GameError::TournamentFull => "This tournament is full",
+            GameError::NotEnoughPlayers => "At least 2 players must register before the start",
+            GameError::InvalidLeague => "A league needs a name and room for 2 to 64 players",
+            GameError::LeagueNotFound => "League not found",
+            GameError::LeagueNotOpen => "Registration for this league is closed",
+            GameError::LeagueFull => "This league is full",
+            GameError::WrongEntryFee => "The amount paid does not match the entry fee",
+            GameError::EntryFeeRequiresSigner => "Paying an entry fee requires a signed block",
+            GameError::TokenNotConfigured => "No fungible token application is configured",
+            GameError::StorageError => "Failed to access application state",
+        }
+    }
+}
+
+impl fmt::Display for GameError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.message())
+    }
+}
+
+impl std::error::Error for GameError {}
+
+/// Trim and validate a player display name
+pub fn validate_player_name(name: &str) -> Result<String, GameError> {
+    let name = name.trim();
+    let length = name.chars().count();
+    if length == 0 || length > MAX_PLAYER_NAME_LEN || name.chars().any(char::is_control) {
+        return Err(GameError::InvalidName);
+    }
+    Ok(name.to_string())
+}
+
+impl Default for EloConfig {
+    fn default() -> Self {
+        Self {
+            initial_rating: DEFAULT_ELO_RATING,
+            k_factor: 32,
+            provisional_k_factor: 64,
+            provisional_games: 10,
+        }
+    }
+}
+
+impl EloConfig {
+    pub fn is_provisional(&self, games_played: u64) -> bool {
+        games_played < self.provisional_games
+    }
+    
+    /// Expected score of a player rated `rating` against `opponent_rating`
+    pub fn expected_score(rating: i32, opponent_rating: i32) -> f64 {
+        1.0 / (1.0 + 10f64.powf(f64::from(opponent_rating - rating) / 400.0))
+    }
+    
+    /// Rating change for a player who scored `score` (1 for a win, 0 for a loss) against
+    /// `opponent_rating`, given the games they had played before this one
+    pub fn rating_delta(&self, rating: i32, opponent_rating: i32, games_played: u64, score: f64) -> i32 {
+        let k_factor = if self.is_provisional(games_played) {
+            self.provisional_k_factor
+        } else {
+            self.k_factor
+        };
+        let expected = Self::expected_score(rating, opponent_rating);
+        (f64::from(k_factor) * (score - expected)).round() as i32
+    }
+}
+
+impl MatchFormat {
+    /// Best of `rounds`: at most `rounds` rounds (draws included), majority of them wins
+    pub fn best_of(rounds: u8) -> Self {
+        Self {
+            wins_to_clinch: rounds / 2 + 1,
+            max_rounds: Some(u16::from(rounds)),
+            max_draws: None,
+            tiebreak: TiebreakRule::MostWins,
+        }
+    }
+    
+    /// First player to win `wins` rounds, with no cap on draws
+    pub fn first_to(wins: u8) -> Self {
+        Self {
+            wins_to_clinch: wins,
+            max_rounds: None,
+            max_draws: None,
+            tiebreak: TiebreakRule::SuddenDeath,
+        }
+    }
+    
+    pub fn validate(&self) -> Result<(), GameError> {
+        let rounds_too_low = self.max_rounds.is_some_and(|max_rounds| max_rounds < u16::from(self.wins_to_clinch));
+        if self.wins_to_clinch == 0 || rounds_too_low || self.max_draws == Some(0) {
+            return Err(GameError::InvalidMatchFormat);
+        }
+        Ok(())
+    }
+    
+    /// Returns true once the round or draw cap has been reached
+    pub fn cap_reached(&self, rounds_played: usize, draws: u16) -> bool {
+        self.max_rounds.is_some_and(|max_rounds| rounds_played >= usize::from(max_rounds))
+            || self.max_draws.is_some_and(|max_draws| draws >= max_draws)
+    }
+}
+
+impl Default for MatchFormat {
+    /// First to 3 round wins
+    fn default() -> Self {
+        Self::first_to(3)
+    }
+}
+
+impl Series {
+    /// Games a player needs to win the series
+    pub fn games_to_win(&self) -> u8 {
+        self.best_of / 2 + 1
+    }
+    
+    /// Returns true once a player has clinched the series
+    pub fn is_finished(&self) -> bool {
+        self.winner.is_some()
+    }
+    
+    /// Count a finished game for `winner`, settling the series once they clinch it.
+    /// Returns true if the series is over.
+    pub fn record_game(&mut self, winner: ChainId) -> bool {
+        if winner == self.player1 {
+            self.player1_wins += 1;
+        } else if winner == self.player2 {
+            self.player2_wins += 1;
+        }
+        
+        if self.player1_wins >= self.games_to_win() {
+            self.winner = Some(self.player1);
+        } else if self.player2_wins >= self.games_to_win() {
+            self.winner = Some(self.player2);
+        }
+        self.is_finished()
+    }
+    
+    /// Seats for the next game: sides alternate, starting with player 1 in the first seat
+    pub fn next_seats(&self) -> (ChainId, ChainId) {
+        if self.rooms.len().is_multiple_of(2) {
+            (self.player1, self.player2)
+        } else {
+            (self.player2, self.player1)
+        }
+    }
+}
+
+impl RoomStatus {
+    /// Returns true if a room may move from this status to `next`
+    pub fn can_transition_to(&self, next: RoomStatus) -> bool {
+        use RoomStatus::*;
+        matches!(
+            (self, next),
+            (WaitingForOpponent, InProgress)
+                | (WaitingForOpponent, Cancelled)
+                | (InProgress, WaitingForOpponent)
+                | (InProgress, AwaitingReveal)
+                | (InProgress, Finished)
+                | (InProgress, Abandoned)
+                | (InProgress, Cancelled)
+                | (AwaitingReveal, InProgress)
+                | (AwaitingReveal, Finished)
+                | (AwaitingReveal, Abandoned)
+        )
+    }
+    
+    /// Returns true while rounds are being played
+    pub fn is_active(&self) -> bool {
+        matches!(self, RoomStatus::InProgress | RoomStatus::AwaitingReveal)
+    }
+    
+    /// Returns true once the room can no longer change
+    pub fn is_closed(&self) -> bool {
+        matches!(self, RoomStatus::Finished | RoomStatus::Abandoned | RoomStatus::Cancelled)
+    }
+}
+
+/// Trim and validate a room alias
+pub fn validate_room_alias(alias: &str) -> Result<String, GameError> {
+    let alias = alias.trim();
+    let length = alias.chars().count();
+    if length == 0
+        || length > MAX_ROOM_ALIAS_LEN
+        || !alias.chars().all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_')
+    {
+        return Err(GameError::InvalidRoomAlias);
+    }
+    Ok(alias.to_string())
+}
+
+/// Weighted checksum of room code digits, modulo 32. Odd weights are invertible modulo 32,
+/// so any single mistyped digit changes the checksum, and so do most swapped neighbours.
+fn room_code_checksum(digits: &[u8]) -> u8 {
+    let sum: u32 = digits.iter()
+        .enumerate()
+        .map(|(index, &digit)| (2 * index as u32 + 1) * u32::from(digit))
+        .sum();
+    (sum % 32) as u8
+}
+
+/// Human-shareable room code for the `counter`-th room: the counter in Crockford base32,
+/// padded to four digits, followed by a checksum digit (e.g. `0001-7` for the first room)
+pub fn room_code(counter: u64) -> String {
+    let mut digits = Vec::new();
+    let mut value = counter;
+    while value > 0 || digits.len() < ROOM_CODE_MIN_DIGITS {
+        digits.push((value % 32) as u8);
+        value /= 32;
+    }
+    digits.reverse();
+    
+    let checksum = room_code_checksum(&digits);
+    let mut code: String = digits.iter().map(|&digit| char::from(ROOM_CODE_ALPHABET[usize::from(digit)])).collect();
+    code.push('-');
+    code.push(char::from(ROOM_CODE_ALPHABET[usize::from(checksum)]));
+    code
+}
+
+/// Parse a room code typed by a player, accepting lower case, missing dashes and the
+/// letters Crockford base32 reads as digits (`I`/`L` for 1, `O` for 0). Returns the
+/// canonical code if the checksum matches.
+pub fn normalize_room_code(input: &str) -> Option<String> {
+    let mut digits = Vec::new();
+    for c in input.trim().chars().filter(|&c| c != '-') {
+        let c = match c.to_ascii_uppercase() {
+            'I' | 'L' => '1',
+            'O' => '0',
+            c => c,
+        };
+        let digit = ROOM_CODE_ALPHABET.iter().position(|&symbol| char::from(symbol) == c)?;
+        digits.push(digit as u8);
+    }
+    
+    let checksum = digits.pop()?;
+    if digits.len() < ROOM_CODE_MIN_DIGITS || digits.len() > 13 || room_code_checksum(&digits) != checksum {
+        return None;
+    }
+    let counter = digits.iter().try_fold(0u64, |value, &digit| value.checked_mul(32)?.checked_add(u64::from(digit)))?;
+    Some(room_code(counter))
+}
+
+/// Compute the commitment a player submits before revealing their choice.
+///
+/// The hash binds the choice to the room, the round and the player's chain so that a
+/// commitment cannot be replayed in another round or copied by the opponent.
+pub fn choice_commitment(
+    room_id: &str,
+    round_number: u16,
+    player_chain: ChainId,
+    choice: Choice,
+    salt: &str,
+) -> String {
+    let preimage = format!("{}:{}:{}:{:?}:{}", room_id, round_number, player_chain, choice, salt);
+    Sha3_256::digest(preimage.as_bytes())
+        .iter()
+        .map(|byte| format!("{:02x}", byte))
+        .collect()
+}
+
+/// Check that a commitment is a hex-encoded SHA3-256 digest, as `choice_commitment`
+/// produces, and return it in lower case
+pub fn validate_commitment(commitment: &str) -> Result<String, GameError> {
+    if commitment.len() != COMMITMENT_LEN || !commitment.chars().all(|c| c.is_ascii_hexdigit()) {
+        return Err(GameError::InvalidCommitment);
+    }
+    Ok(commitment.to_ascii_lowercase())
+}
+
+impl RoomFilter {
+    /// Whether `room` meets every criterion of the filter
+    pub fn matches(&self, room: &GameRoom) -> bool {
+        self.status.as_ref().is_none_or(|statuses| statuses.contains(&room.status))
+            && self.private.is_none_or(|private| room.private == private)
+            && self.player.is_none_or(|player| room.get_player_number(player).is_some())
+            && self.created_after.is_none_or(|after| room.created_at >= after)
+            && self.created_before.is_none_or(|before| room.created_at < before)
+            && self.wins_to_clinch.is_none_or(|wins| room.format.wins_to_clinch == wins)
+            && self.format.is_none_or(|format| room.format == format)
+            && self.room_id_prefix.as_ref().is_none_or(|prefix| {
+                let prefix = prefix.to_ascii_uppercase();
+                room.room_id.to_ascii_uppercase().starts_with(&prefix)
+                    || room.alias.as_ref().is_some_and(|alias| alias.to_ascii_uppercase().starts_with(&prefix))
+            })
+    }
+    
+    /// Statuses the filter is limited to when none of them is closed, so that the search
+    /// only has to look at open rooms
+    pub fn open_statuses(&self) -> Option<&[RoomStatus]> {
+        self.status.as_deref().filter(|statuses| statuses.iter().all(|status| !status.is_closed()))
+    }
+}
+
+impl RoomOrder {
+    /// Compare rooms by their creation time and room ID in this order
+    pub fn compare(self, a: (u64, &str), b: (u64, &str)) -> Ordering {
+        match self {
+            RoomOrder::Newest => b.cmp(&a),
+            RoomOrder::Oldest => a.cmp(&b),
+            RoomOrder::RoomId => a.1.cmp(b.1),
+        }
+    }
+    
+    /// Sort `rooms` in this order
+    pub fn sort(self, rooms: &mut [GameRoom]) {
+        rooms.sort_by(|a, b| self.compare((a.created_at, &a.room_id), (b.created_at, &b.room_id)));
+    }
+    
+    /// Position in `rooms`, sorted in this order, of the first room after the room at
+    /// `cursor`, which need not be in `rooms` any more
+    pub fn resume_index(self, rooms: &[GameRoom], cursor: (u64, &str)) -> usize {
+        rooms.partition_point(|room| self.compare((room.created_at, &room.room_id), cursor).is_le())
+    }
+}
+
+/// Hash of a room invite code, the form in which the room creator sends the code and the
+/// leaderboard chain stores it. Joiners send the code itself, and the hash is unsalted, so
+/// the code is readable in their messages and a short code can be guessed from the hash.
+pub fn invite_code_hash(invite_code: &str) -> String {
+    let preimage = format!("invite:{}", invite_code);
+    Sha3_256::digest(preimage.as_bytes())
+        .iter()
+        .map(|byte| format!("{:02x}", byte))
+        .collect()
+}
+
+/// Check that `stake` pays exactly `entry_fee` in `currency`; free entry takes no stake
+pub fn check_stake(stake: Option<&Stake>, entry_fee: Amount, currency: Currency) -> Result<(), GameError> {
+    let paid = match stake {
+        Some(stake) if stake.currency != currency => return Err(GameError::WrongEntryFee),
+        Some(stake) => stake.amount,
+        None => Amount::ZERO,
+    };
+    if paid == entry_fee {
+        Ok(())
+    } else {
+        Err(GameError::WrongEntryFee)
+    }
+}
+
+#[async_graphql::ComplexObject]
+impl GameRoom {
+    /// Whether joiners outside the allow-list must present an invite code
+    async fn requires_invite_code(&self) -> bool {
+        self.invite_code_hash.is_some()
+    }
+}
+
 impl GameRoom {
-    pub fn new(room_id: String, timestamp: u64, private: bool) -> Self {
+    pub fn new(
+        room_id: String,
+        timestamp: u64,
+        private: bool,
+        format: MatchFormat,
+        move_timeout_secs: u64,
+        timeout_penalty: TimeoutPenalty,
+    ) -> Self {
         Self {
             room_id,
+            alias: None,
+            host: None,
             player1: None,
             player2: None,
             player1_name: None,
             player2_name: None,
+            player1_commitment: None,
+            player2_commitment: None,
             player1_choice: None,
             player2_choice: None,
+            status: RoomStatus::WaitingForOpponent,
             game_result: GameResult {
                 player1_wins: 0,
                 player2_wins: 0,
                 draws: 0,
                 winner: None,
                 is_finished: false,
+                resigned_by: None,
             },
             created_at: timestamp,
             round_number: 1,
+            format,
             private,
             round_history: Vec::new(),
+            move_timeout_secs,
+            timeout_penalty,
+            phase_deadline: None,
+            allowed_players: Vec::new(),
+            invite_code_hash: None,
+            rematch_offered_by: None,
+            previous_room: None,
+            next_room: None,
+            series_id: None,
+            tournament_id: None,
+            league_id: None,
+            entry_fee: Amount::ZERO,
+            currency: Currency::Native,
+            pot: Amount::ZERO,
+            stakes: Vec::new(),
         }
     }
     
@@ -226,26 +1188,76 @@ impl GameRoom {
         self.player1.is_some() && self.player2.is_some()
     }
     
-    pub fn can_join(&self, chain_id: ChainId) -> bool {
-        !self.game_result.is_finished && 
-        (self.player1.is_none() || 
-         (self.player2.is_none() && self.player1 != Some(chain_id)))
+    pub fn check_join(&self, chain_id: ChainId, invite_code: Option<&str>) -> Result<(), GameError> {
+        if self.get_player_number(chain_id).is_some() {
+            return Err(GameError::AlreadyInRoom);
+        }
+        if self.is_full() {
+            return Err(GameError::RoomFull);
+        }
+        if self.status != RoomStatus::WaitingForOpponent {
+            return Err(GameError::InvalidRoomState);
+        }
+        self.check_invite(chain_id, invite_code)
     }
     
-    pub fn add_player(&mut self, chain_id: ChainId) -> bool {
-        if !self.can_join(chain_id) {
-            return false;
+    /// Invited chains may always join; anyone else needs the invite code if the room has one
+    fn check_invite(&self, chain_id: ChainId, invite_code: Option<&str>) -> Result<(), GameError> {
+        if self.allowed_players.contains(&chain_id) {
+            return Ok(());
+        }
+        match (&self.invite_code_hash, invite_code) {
+            (None, _) if self.allowed_players.is_empty() => Ok(()),
+            (Some(hash), Some(code)) if *hash == invite_code_hash(code) => Ok(()),
+            (Some(_), Some(_)) => Err(GameError::InvalidInviteCode),
+            _ => Err(GameError::NotInvited),
         }
+    }
+    
+    pub fn can_join(&self, chain_id: ChainId, invite_code: Option<&str>) -> bool {
+        self.check_join(chain_id, invite_code).is_ok()
+    }
+    
+    /// Restrict who may join; only private rooms can be invite-only
+    pub fn set_access(&mut self, access: RoomAccess) -> Result<(), GameError> {
+        if !access.is_open() && !self.private {
+            return Err(GameError::InviteRequiresPrivateRoom);
+        }
+        self.allowed_players = access.allowed_players;
+        self.invite_code_hash = access.invite_code_hash;
+        Ok(())
+    }
+    
+    pub fn add_player(&mut self, chain_id: ChainId, invite_code: Option<&str>) -> Result<(), GameError> {
+        self.check_join(chain_id, invite_code)?;
         
         if self.player1.is_none() {
             self.player1 = Some(chain_id);
-        } else if self.player2.is_none() {
-            self.player2 = Some(chain_id);
         } else {
-            return false;
+            self.player2 = Some(chain_id);
         }
         
-        true
+        if self.is_full() {
+            self.transition(RoomStatus::InProgress)?;
+        }
+        Ok(())
+    }
+    
+    /// Move the room to `next` if the lifecycle allows it; all status changes go through here
+    pub fn transition(&mut self, next: RoomStatus) -> Result<(), GameError> {
+        if !self.status.can_transition_to(next) {
+            return Err(GameError::InvalidRoomState);
+        }
+        self.status = next;
+        if next.is_closed() {
+            self.phase_deadline = None;
+        }
+        Ok(())
+    }
+    
+    /// Returns true if the room should be listed in available rooms
+    pub fn is_listed(&self) -> bool {
+        self.status == RoomStatus::WaitingForOpponent && !self.private
     }
     
     pub fn get_player_number(&self, chain_id: ChainId) -> Option<u8> {
@@ -262,75 +1274,337 @@ impl GameRoom {
         self.player1_choice.is_some() && self.player2_choice.is_some()
     }
     
-    pub fn set_choice(&mut self, chain_id: ChainId, choice: Choice) -> bool {
-        match self.get_player_number(chain_id) {
-            Some(1) => {
-                self.player1_choice = Some(choice);
-                true
-            }
-            Some(2) => {
-                self.player2_choice = Some(choice);
-                true
+    pub fn both_players_committed(&self) -> bool {
+        self.player1_commitment.is_some() && self.player2_commitment.is_some()
+    }
+    
+    /// Returns true once any player has committed a choice
+    pub fn has_choices(&self) -> bool {
+        !self.round_history.is_empty()
+            || self.player1_commitment.is_some()
+            || self.player2_commitment.is_some()
+    }
+    
+    /// Returns true for series games, tournament matches and league pairings, whose seats are
+    /// assigned by the leaderboard chain
+    pub fn is_scheduled(&self) -> bool {
+        self.series_id.is_some() || self.tournament_id.is_some() || self.league_id.is_some()
+    }
+    
+    /// Free a player's seat before any choice was made, reopening the room for joiners
+    pub fn remove_player(&mut self, chain_id: ChainId) -> Result<(), GameError> {
+        let player_number = self.get_player_number(chain_id).ok_or(GameError::NotAPlayer)?;
+        if self.has_choices() || self.is_scheduled() {
+            return Err(GameError::MatchAlreadyStarted);
+        }
+        match self.status {
+            RoomStatus::WaitingForOpponent => {}
+            RoomStatus::InProgress => {
+                self.transition(RoomStatus::WaitingForOpponent)?;
+                self.phase_deadline = None;
             }
-            Some(_) => false, // Invalid player number
-            None => false,
+            _ => return Err(GameError::InvalidRoomState),
+        }
+        
+        // Keep the remaining player in the first seat
+        if player_number == 1 {
+            self.player1 = self.player2.take();
+            self.player1_name = self.player2_name.take();
+        } else {
+            self.player2 = None;
+            self.player2_name = None;
+        }
+        Ok(())
+    }
+    
+    /// Concede an active match, awarding the game to the opponent. Returns the winner.
+    pub fn resign(&mut self, chain_id: ChainId) -> Result<ChainId, GameError> {
+        let opponent = match self.get_player_number(chain_id) {
+            Some(1) => self.player2,
+            Some(2) => self.player1,
+            _ => return Err(GameError::NotAPlayer),
+        };
+        if !self.status.is_active() {
+            return Err(GameError::InvalidRoomState);
+        }
+        let winner = opponent.ok_or(GameError::InvalidRoomState)?;
+        
+        self.transition(RoomStatus::Finished)?;
+        self.game_result.winner = Some(winner);
+        self.game_result.is_finished = true;
+        self.game_result.resigned_by = Some(chain_id);
+        Ok(winner)
+    }
+    
+    /// Offer the opponent a rematch once the game is finished
+    pub fn offer_rematch(&mut self, chain_id: ChainId) -> Result<(), GameError> {
+        if self.get_player_number(chain_id).is_none() {
+            return Err(GameError::NotAPlayer);
+        }
+        if self.status != RoomStatus::Finished {
+            return Err(GameError::InvalidRoomState);
+        }
+        if self.next_room.is_some() {
+            return Err(GameError::RematchAlreadyStarted);
+        }
+        if self.entry_fee > Amount::ZERO {
+            return Err(GameError::WageredRematch);
+        }
+        self.rematch_offered_by = Some(chain_id);
+        Ok(())
+    }
+    
+    /// Check that `chain_id` may accept a pending rematch offer from the opponent
+    pub fn check_rematch_accept(&self, chain_id: ChainId) -> Result<(), GameError> {
+        if self.get_player_number(chain_id).is_none() {
+            return Err(GameError::NotAPlayer);
+        }
+        if self.next_room.is_some() {
+            return Err(GameError::RematchAlreadyStarted);
+        }
+        match self.rematch_offered_by {
+            Some(offered_by) if offered_by != chain_id => Ok(()),
+            _ => Err(GameError::NoRematchOffered),
+        }
+    }
+    
+    /// Build the rematch room: same players with sides swapped, same host, format, privacy
+    /// and timeouts, linked back to this room. Rooms with an entry fee are not rematched,
+    /// since nobody has paid the fee for the new room.
+    pub fn rematch(&self, room_id: String, timestamp: u64) -> Result<GameRoom, GameError> {
+        let (Some(player1), Some(player2)) = (self.player2, self.player1) else {
+            return Err(GameError::InvalidRoomState);
+        };
+        if self.entry_fee > Amount::ZERO {
+            return Err(GameError::WageredRematch);
+        }
+        
+        let mut room = GameRoom::new(
+            room_id,
+            timestamp,
+            self.private,
+            self.format,
+            self.move_timeout_secs,
+            self.timeout_penalty,
+        );
+        room.host = self.host;
+        room.allowed_players = self.allowed_players.clone();
+        room.invite_code_hash = self.invite_code_hash.clone();
+        room.previous_room = Some(self.room_id.clone());
+        
+        room.add_player(player1, None)?;
+        room.add_player(player2, None)?;
+        room.player1_name = self.player2_name.clone();
+        room.player2_name = self.player1_name.clone();
+        Ok(room)
+    }
+    
+    /// Check that a player taking a seat paid exactly the entry fee
+    pub fn check_entry_fee(&self, stake: Option<&Stake>) -> Result<(), GameError> {
+        check_stake(stake, self.entry_fee, self.currency)
+    }
+    
+    /// Hold a seated player's entry fee in the pot
+    pub fn escrow(&mut self, stake: Stake) {
+        self.pot = self.pot.saturating_add(stake.amount);
+        self.stakes.push(stake);
+    }
+    
+    /// Take a player's entry fee back out of the pot, for a refund when they leave
+    pub fn release_stake(&mut self, chain_id: ChainId) -> Option<Stake> {
+        let index = self.stakes.iter().position(|stake| stake.player == chain_id)?;
+        let stake = self.stakes.remove(index);
+        self.pot = self.pot.saturating_sub(stake.amount);
+        Some(stake)
+    }
+    
+    /// Take all entry fees still in escrow, to pay them out or refund them
+    pub fn take_stakes(&mut self) -> Vec<Stake> {
+        std::mem::take(&mut self.stakes)
+    }
+    
+    /// Close the room before both players have committed their first choice. Past that point
+    /// the host could see the opponent's reveal before cancelling, so the round must be played.
+    /// Series, tournament and league games are never cancelled, as their competition waits
+    /// for the result.
+    pub fn cancel(&mut self) -> Result<(), GameError> {
+        if !self.round_history.is_empty() || self.both_players_committed() || self.is_scheduled() {
+            return Err(GameError::MatchAlreadyStarted);
+        }
+        self.transition(RoomStatus::Cancelled)
+    }
+    
+    pub fn commit_choice(&mut self, chain_id: ChainId, commitment: String) -> Result<(), GameError> {
+        let commitment = validate_commitment(&commitment)?;
+        let slot = match self.get_player_number(chain_id) {
+            Some(1) => &mut self.player1_commitment,
+            Some(2) => &mut self.player2_commitment,
+            _ => return Err(GameError::NotAPlayer),
+        };
+        if slot.is_some() {
+            return Err(GameError::AlreadyCommitted); // Commitments cannot be changed
+        }
+        if self.status != RoomStatus::InProgress {
+            return Err(GameError::InvalidRoomState);
+        }
+        *slot = Some(commitment);
+        
+        if self.both_players_committed() {
+            self.transition(RoomStatus::AwaitingReveal)?;
+        }
+        Ok(())
+    }
+    
+    pub fn reveal_choice(&mut self, chain_id: ChainId, choice: Choice, salt: &str) -> Result<(), GameError> {
+        let expected = choice_commitment(&self.room_id, self.round_number, chain_id, choice, salt);
+        let (commitment, revealed) = match self.get_player_number(chain_id) {
+            Some(1) => (&self.player1_commitment, &mut self.player1_choice),
+            Some(2) => (&self.player2_commitment, &mut self.player2_choice),
+            _ => return Err(GameError::NotAPlayer),
+        };
+        if self.status != RoomStatus::AwaitingReveal {
+            return Err(GameError::InvalidRoomState);
+        }
+        if revealed.is_some() {
+            return Err(GameError::AlreadyRevealed);
+        }
+        if commitment.as_deref() != Some(expected.as_str()) {
+            return Err(GameError::CommitmentMismatch);
+        }
+        *revealed = Some(choice);
+        Ok(())
+    }
+    
+    /// Start the clock for the current round phase
+    pub fn reset_deadline(&mut self, now: u64) {
+        self.phase_deadline = if !self.status.is_active() {
+            None
+        } else {
+            Some(now.saturating_add(self.move_timeout_secs.saturating_mul(1_000_000)))
+        };
+    }
+    
+    /// Returns true if the current round phase has run past its deadline
+    pub fn deadline_passed(&self, now: u64) -> bool {
+        self.status.is_active() && self.phase_deadline.is_some_and(|deadline| now > deadline)
+    }
+    
+    /// Return the player who missed the current phase deadline while the opponent acted
+    pub fn timed_out_player(&self, now: u64) -> Option<ChainId> {
+        if !self.deadline_passed(now) {
+            return None;
+        }
+        
+        let (player1_acted, player2_acted) = match self.status {
+            RoomStatus::AwaitingReveal => (self.player1_choice.is_some(), self.player2_choice.is_some()),
+            _ => (self.player1_commitment.is_some(), self.player2_commitment.is_some()),
+        };
+        match (player1_acted, player2_acted) {
+            (true, false) => self.player2,
+            (false, true) => self.player1,
+            _ => None, // Nobody is waiting on an opponent
         }
     }
     
     pub fn calculate_round_result(&mut self) -> Option<(RoundResult, Option<ChainId>)> {
         if let (Some(choice1), Some(choice2)) = (self.player1_choice, self.player2_choice) {
             let result = choice1.compare(&choice2);
-            
-            let winner = match result {
-                RoundResult::Win => self.player1,
-                RoundResult::Lose => self.player2,
-                RoundResult::Draw => None,
-            };
-            
-            // Add round to history before clearing choices
-            self.round_history.push(RoundHistory {
-                round_number: self.round_number,
-                player1_choice: choice1,
-                player2_choice: choice2,
-                result,
-                winner,
-            });
-            
-            // Update game result based on round result
-            match result {
-                RoundResult::Win => {
-                    self.game_result.player1_wins += 1;
-                    if self.game_result.player1_wins >= 3 {
-                        self.game_result.winner = self.player1;
-                        self.game_result.is_finished = true;
-                    }
-                }
-                RoundResult::Lose => {
-                    self.game_result.player2_wins += 1;
-                    if self.game_result.player2_wins >= 3 {
-                        self.game_result.winner = self.player2;
-                        self.game_result.is_finished = true;
-                    }
-                }
-                RoundResult::Draw => {
-                    self.game_result.draws += 1;
-                }
-            }
-            
-            // Clear choices for next round
-            self.player1_choice = None;
-            self.player2_choice = None;
-            
-            // Increment round number if game not finished
-            if !self.game_result.is_finished {
-                self.round_number += 1;
-            }
-            
-            Some((result, winner))
+            Some(self.record_round(Some(choice1), Some(choice2), result, None, false))
         } else {
             None
         }
     }
+    
+    /// Award the current round to the opponent of `loser`, or the whole game depending on the
+    /// room's timeout penalty
+    pub fn forfeit_round(&mut self, loser: ChainId) -> Option<(RoundResult, Option<ChainId>)> {
+        let result = match self.get_player_number(loser) {
+            Some(1) => RoundResult::Lose,
+            Some(2) => RoundResult::Win,
+            _ => return None,
+        };
+        
+        let (choice1, choice2) = (self.player1_choice, self.player2_choice);
+        let ends_game = self.timeout_penalty == TimeoutPenalty::ForfeitGame;
+        Some(self.record_round(choice1, choice2, result, Some(loser), ends_game))
+    }
+    
+    fn record_round(
+        &mut self,
+        choice1: Option<Choice>,
+        choice2: Option<Choice>,
+        result: RoundResult,
+        forfeited_by: Option<ChainId>,
+        ends_game: bool,
+    ) -> (RoundResult, Option<ChainId>) {
+        let winner = match result {
+            RoundResult::Win => self.player1,
+            RoundResult::Lose => self.player2,
+            RoundResult::Draw => None,
+        };
+        
+        // Past the round or draw cap, the next decisive round settles the game
+        let sudden_death = self.format.cap_reached(self.round_history.len(), self.game_result.draws);
+        
+        // Add round to history before clearing choices
+        self.round_history.push(RoundHistory {
+            round_number: self.round_number,
+            player1_choice: choice1,
+            player2_choice: choice2,
+            result,
+            winner,
+            forfeited_by,
+        });
+        
+        // Update game result based on round result
+        match result {
+            RoundResult::Win => self.game_result.player1_wins = self.game_result.player1_wins.saturating_add(1),
+            RoundResult::Lose => self.game_result.player2_wins = self.game_result.player2_wins.saturating_add(1),
+            RoundResult::Draw => self.game_result.draws = self.game_result.draws.saturating_add(1),
+        }
+        
+        let wins_to_clinch = self.format.wins_to_clinch;
+        if self.game_result.player1_wins >= wins_to_clinch {
+            self.finish(self.player1);
+        } else if self.game_result.player2_wins >= wins_to_clinch {
+            self.finish(self.player2);
+        } else if (ends_game || sudden_death) && winner.is_some() {
+            self.finish(winner);
+        } else if self.format.tiebreak == TiebreakRule::MostWins
+            && self.format.cap_reached(self.round_history.len(), self.game_result.draws)
+        {
+            // Cap just reached: the leader takes the game, otherwise play on in sudden death
+            let (player1_wins, player2_wins) = (self.game_result.player1_wins, self.game_result.player2_wins);
+            if player1_wins > player2_wins {
+                self.finish(self.player1);
+            } else if player2_wins > player1_wins {
+                self.finish(self.player2);
+            }
+        }
+        
+        // Clear commitments and choices for next round
+        self.player1_commitment = None;
+        self.player2_commitment = None;
+        self.player1_choice = None;
+        self.player2_choice = None;
+        if self.status == RoomStatus::AwaitingReveal {
+            let _ = self.transition(RoomStatus::InProgress);
+        }
+        
+        // Increment round number if game not finished
+        if !self.game_result.is_finished {
+            self.round_number = self.round_number.saturating_add(1);
+        }
+        
+        (result, winner)
+    }
+    
+    fn finish(&mut self, winner: Option<ChainId>) {
+        if self.transition(RoomStatus::Finished).is_ok() {
+            self.game_result.winner = winner;
+            self.game_result.is_finished = true;
+        }
+    }
 }
 
 impl LeaderboardEntry {
@@ -341,6 +1615,10 @@ impl LeaderboardEntry {
             wins: 0,
             losses: 0,
             total_games: 0,
+            rating: DEFAULT_ELO_RATING,
+            provisional: true,
+            series_played: 0,
+            series_won: 0,
         }
     }
     
@@ -351,6 +1629,10 @@ impl LeaderboardEntry {
             wins: 0,
             losses: 0,
             total_games: 0,
+            rating: DEFAULT_ELO_RATING,
+            provisional: true,
+            series_played: 0,
+            series_won: 0,
         }
     }
     
@@ -371,4 +1653,225 @@ impl LeaderboardEntry {
             0.0
         }
     }
-}
\ No newline at end of file
+    
+    /// Index key ranking the entry by wins, then win rate, then total games, best first.
+    /// Each number is inverted and written as fixed-width hex, so keys sort in ranking order;
+    /// the chain ID breaks ties and keeps keys unique.
+    pub fn wins_rank_key(&self) -> String {
+        // The bits of a non-negative f64 order like the number itself
+        format!("{:016x}{:016x}{:016x}{}", !self.wins, !self.win_rate().to_bits(), !self.total_games, self.chain_id)
+    }
+    
+    /// Index key ranking the entry by rating, then wins, best first
+    pub fn rating_rank_key(&self) -> String {
+        // Flipping the sign bit orders ratings as unsigned numbers
+        let rating = (self.rating as u32) ^ 0x8000_0000;
+        format!("{:08x}{:016x}{}", !rating, !self.wins, self.chain_id)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_utils::{chain, room};
+    
+    fn seated_room() -> GameRoom {
+        let mut room = room("0001-7".to_string(), 0);
+        room.add_player(chain(1), None).unwrap();
+        room.add_player(chain(2), None).unwrap();
+        room
+    }
+    
+    #[test]
+    fn choice_commitment() {
+        let commitment = super::choice_commitment("0001-7", 1, chain(1), Choice::Rock, "salt");
+        assert_eq!(commitment.len(), 64);
+        assert_eq!(validate_commitment(&commitment), Ok(commitment.clone()));
+        assert_eq!(validate_commitment(&commitment.to_ascii_uppercase()), Ok(commitment.clone()));
+        
+        // Bound to the room, round, player, choice and salt
+        assert_ne!(commitment, super::choice_commitment("0002-E", 1, chain(1), Choice::Rock, "salt"));
+        assert_ne!(commitment, super::choice_commitment("0001-7", 2, chain(1), Choice::Rock, "salt"));
+        assert_ne!(commitment, super::choice_commitment("0001-7", 1, chain(2), Choice::Rock, "salt"));
+        assert_ne!(commitment, super::choice_commitment("0001-7", 1, chain(1), Choice::Paper, "salt"));
+        assert_ne!(commitment, super::choice_commitment("0001-7", 1, chain(1), Choice::Rock, "pepper"));
+        
+        for invalid in ["", "abc", &commitment[1..], &format!("{}0", commitment), &"g".repeat(64)] {
+            assert_eq!(validate_commitment(invalid), Err(GameError::InvalidCommitment));
+        }
+    }
+    
+    #[test]
+    fn commit_and_reveal() {
+        let mut room = seated_room();
+        let round = room.round_number;
+        assert_eq!(room.commit_choice(chain(1), String::new()), Err(GameError::InvalidCommitment));
+        assert_eq!(room.commit_choice(chain(1), "x".repeat(1 << 20)), Err(GameError::InvalidCommitment));
+        
+        let commitment1 = super::choice_commitment("0001-7", round, chain(1), Choice::Rock, "one");
+        let commitment2 = super::choice_commitment("0001-7", round, chain(2), Choice::Paper, "two");
+        room.commit_choice(chain(1), commitment1.to_ascii_uppercase()).unwrap();
+        assert_eq!(room.commit_choice(chain(1), commitment1.clone()), Err(GameError::AlreadyCommitted));
+        room.commit_choice(chain(2), commitment2).unwrap();
+        assert_eq!(room.status, RoomStatus::AwaitingReveal);
+        
+        // Another choice, another salt or the opponent's reveal does not open the commitment
+        assert_eq!(room.reveal_choice(chain(1), Choice::Scissors, "one"), Err(GameError::CommitmentMismatch));
+        assert_eq!(room.reveal_choice(chain(1), Choice::Rock, "two"), Err(GameError::CommitmentMismatch));
+        assert_eq!(room.reveal_choice(chain(2), Choice::Rock, "one"), Err(GameError::CommitmentMismatch));
+        assert_eq!(room.reveal_choice(chain(3), Choice::Rock, "one"), Err(GameError::NotAPlayer));
+        
+        room.reveal_choice(chain(1), Choice::Rock, "one").unwrap();
+        assert_eq!(room.reveal_choice(chain(1), Choice::Rock, "one"), Err(GameError::AlreadyRevealed));
+        room.reveal_choice(chain(2), Choice::Paper, "two").unwrap();
+    }
+    
+    #[test]
+    fn cancel() {
+        let mut room = seated_room();
+        room.commit_choice(chain(1), super::choice_commitment("0001-7", 1, chain(1), Choice::Rock, "one")).unwrap();
+        room.cancel().unwrap();
+        assert_eq!(room.status, RoomStatus::Cancelled);
+        
+        let mut room = seated_room();
+        room.commit_choice(chain(1), super::choice_commitment("0001-7", 1, chain(1), Choice::Rock, "one")).unwrap();
+        room.commit_choice(chain(2), super::choice_commitment("0001-7", 1, chain(2), Choice::Paper, "two")).unwrap();
+        assert_eq!(room.cancel(), Err(GameError::MatchAlreadyStarted));
+        
+        let mut room = seated_room();
+        room.tournament_id = Some("1".to_string());
+        assert_eq!(room.cancel(), Err(GameError::MatchAlreadyStarted));
+        assert_eq!(room.status, RoomStatus::InProgress);
+    }
+    
+    #[test]
+    fn room_code_round_trip() {
+        assert_eq!(room_code(0), "0000-0");
+        assert_eq!(room_code(1), "0001-7");
+        assert_eq!(room_code(32 * 32 * 32 * 32), "10000-1");
+        for counter in (0..5000).chain([1 << 20, 1 << 40, u64::MAX]) {
+            let code = room_code(counter);
+            assert_eq!(normalize_room_code(&code), Some(code));
+        }
+    }
+    
+    #[test]
+    fn room_code_loose_input() {
+        for input in ["0001-7", "00017", "0-0-01-7", " 0001-7 ", "oooi-7", "OOOL7", "ooo1-7"] {
+            assert_eq!(normalize_room_code(input).as_deref(), Some("0001-7"), "{}", input);
+        }
+        let code = room_code(1_000_000);
+        assert_eq!(normalize_room_code(&code.to_ascii_lowercase()), Some(code));
+    }
+    
+    #[test]
+    fn room_code_rejects_typos() {
+        for counter in (0..2000).step_by(7).chain([1 << 40, u64::MAX]) {
+            let digits: Vec<u8> = room_code(counter).bytes().filter(|&c| c != b'-').collect();
+            for position in 0..digits.len() {
+                for &symbol in ROOM_CODE_ALPHABET.iter().filter(|&&symbol| symbol != digits[position]) {
+                    let mut typo = digits.clone();
+                    typo[position] = symbol;
+                    let typo = String::from_utf8(typo).unwrap();
+                    assert_eq!(normalize_room_code(&typo), None, "{}", typo);
+                }
+            }
+        }
+        
+        let short = [0, 0, 1];
+        let short_code = format!("001-{}", char::from(ROOM_CODE_ALPHABET[usize::from(room_code_checksum(&short))]));
+        for input in ["", "-", "0001", "0001-U", "0001-!", "0001-7-7", short_code.as_str()] {
+            assert_eq!(normalize_room_code(input), None, "{}", input);
+        }
+    }
+    
+    #[test]
+    fn rooms_resume_after_stale_cursor() {
+        let rooms: Vec<GameRoom> = [(20, 1), (10, 2), (20, 3), (30, 4), (20, 5)].into_iter()
+            .map(|(created_at, counter)| room(room_code(counter), created_at))
+            .collect();
+        for order in [RoomOrder::Newest, RoomOrder::Oldest, RoomOrder::RoomId] {
+            let mut sorted = rooms.clone();
+            order.sort(&mut sorted);
+            for (position, room) in sorted.iter().enumerate() {
+                let cursor = (room.created_at, room.room_id.as_str());
+                assert_eq!(order.resume_index(&sorted, cursor), position + 1);
+                
+                // The cursor's room leaves the results before the next page is read
+                let mut remaining = sorted.clone();
+                remaining.remove(position);
+                assert_eq!(order.resume_index(&remaining, cursor), position, "{:?}", order);
+            }
+        }
+        
+        let mut newest = rooms.clone();
+        RoomOrder::Newest.sort(&mut newest);
+        let created: Vec<u64> = newest.iter().map(|room| room.created_at).collect();
+        assert_eq!(created, [30, 20, 20, 20, 10]);
+        assert_eq!(newest[1].room_id, room_code(5));
+    }
+    
+    /// Leaderboard entries with small, heavily tied stats, plus extreme values
+    fn leaderboard_entries() -> Vec<LeaderboardEntry> {
+        let mut seed: u64 = 7;
+        let mut next = |bound: u64| {
+            seed = seed.wrapping_mul(6364136223846793005).wrapping_add(1442695040888963407);
+            (seed >> 33) % bound
+        };
+        let mut entries: Vec<LeaderboardEntry> = (0..=255).map(|n| {
+            let mut entry = LeaderboardEntry::new(chain(n));
+            entry.wins = next(6);
+            entry.losses = next(6);
+            entry.total_games = entry.wins + entry.losses;
+            entry.rating = next(400) as i32 - 200;
+            entry
+        }).collect();
+        entries[0].rating = i32::MIN;
+        entries[1].rating = i32::MAX;
+        entries[2].wins = u64::MAX / 2;
+        entries[2].total_games = u64::MAX / 2;
+        entries
+    }
+    
+    fn chain_ids(entries: &[LeaderboardEntry]) -> Vec<ChainId> {
+        entries.iter().map(|entry| entry.chain_id).collect()
+    }
+    
+    #[test]
+    fn wins_rank_key_order() {
+        let mut expected = leaderboard_entries();
+        expected.sort_by(|a, b| {
+            b.wins.cmp(&a.wins)
+                .then_with(|| b.win_rate().total_cmp(&a.win_rate()))
+                .then_with(|| b.total_games.cmp(&a.total_games))
+                .then_with(|| a.chain_id.to_string().cmp(&b.chain_id.to_string()))
+        });
+        let mut indexed = leaderboard_entries();
+        indexed.sort_by_key(LeaderboardEntry::wins_rank_key);
+        assert_eq!(chain_ids(&indexed), chain_ids(&expected));
+    }
+    
+    #[test]
+    fn rating_rank_key_order() {
+        let mut expected = leaderboard_entries();
+        expected.sort_by(|a, b| {
+            b.rating.cmp(&a.rating)
+                .then_with(|| b.wins.cmp(&a.wins))
+                .then_with(|| a.chain_id.to_string().cmp(&b.chain_id.to_string()))
+        });
+        let mut indexed = leaderboard_entries();
+        indexed.sort_by_key(LeaderboardEntry::rating_rank_key);
+        assert_eq!(chain_ids(&indexed), chain_ids(&expected));
+        assert_eq!(indexed.first().unwrap().rating, i32::MAX);
+        assert_eq!(indexed.last().unwrap().rating, i32::MIN);
+    }
+    
+    #[test]
+    fn rank_keys_break_ties_by_chain_id() {
+        let first = LeaderboardEntry::new(chain(1));
+        let second = LeaderboardEntry::new(chain(2));
+        assert!(first.wins_rank_key() < second.wins_rank_key());
+        assert!(first.rating_rank_key() < second.rating_rank_key());
+        assert_ne!(first.wins_rank_key(), second.wins_rank_key());
+    }
+}
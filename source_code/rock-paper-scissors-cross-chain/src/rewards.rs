@@ -0,0 +1,209 @@
+// Copyright (c) Zefchain Labs, Inc.
+// SPDX-License-Identifier: Apache-2.0
+
+/*! Reward points for winning public games
+
+The winner of every public game is credited `base_points`, multiplied by the bonus of the
+highest win-streak milestone they reached. Points credited within one reward day are capped,
+so farming games cannot earn more than `daily_cap` a day.
+*/
+
+use async_graphql::SimpleObject;
+use linera_sdk::linera_base_types::ChainId;
+use serde::{Deserialize, Serialize};
+
+// Multiplier applied once a player's win streak reaches `streak`
+#[derive(Debug, Clone, Copy, Deserialize, Serialize, PartialEq, Eq, SimpleObject)]
+pub struct StreakBonus {
+    pub streak: u64,
+    pub multiplier_percent: u64, // 150 pays one and a half times the base points
+}
+
+// Reward system configuration
+#[derive(Debug, Clone, Deserialize, Serialize, SimpleObject)]
+#[serde(default)]
+pub struct RewardConfig {
+    pub base_points: u64, // Points for a win without a streak bonus
+    pub streak_bonuses: Vec<StreakBonus>,
+    pub daily_cap: Option<u64>, // Most points one player can earn per reward day, none for no cap
+    pub day_secs: u64, // Length of a reward day
+}
+
+impl Default for RewardConfig {
+    fn default() -> Self {
+        Self {
+            base_points: 10,
+            streak_bonuses: vec![
+                StreakBonus { streak: 3, multiplier_percent: 150 },
+                StreakBonus { streak: 5, multiplier_percent: 200 },
+                StreakBonus { streak: 10, multiplier_percent: 300 },
+            ],
+            daily_cap: Some(500),
+            day_secs: 24 * 60 * 60,
+        }
+    }
+}
+
+impl RewardConfig {
+    /// Multiplier for a win that brings the streak to `streak`, in percent
+    pub fn multiplier_percent(&self, streak: u64) -> u64 {
+        self.streak_bonuses.iter()
+            .filter(|bonus| streak >= bonus.streak)
+            .max_by_key(|bonus| bonus.streak)
+            .map_or(100, |bonus| bonus.multiplier_percent)
+    }
+    
+    /// Points for a win that brings the streak to `streak`, before the daily cap
+    pub fn points_for_win(&self, streak: u64) -> u64 {
+        self.base_points.saturating_mul(self.multiplier_percent(streak)) / 100
+    }
+    
+    /// Reward day `timestamp` falls in
+    pub fn day(&self, timestamp: u64) -> u64 {
+        timestamp / self.day_secs.max(1).saturating_mul(1_000_000)
+    }
+}
+
+// Reward points earned by a player
+#[derive(Debug, Clone, Serialize, Deserialize, SimpleObject)]
+pub struct RewardBalance {
+    pub chain_id: ChainId,
+    pub balance: u64, // All points earned so far
+    pub wins_rewarded: u64,
+    pub day: u64, // Reward day of the last credit
+    pub earned_today: u64, // Points credited during `day`
+    pub last_reward: u64, // Points credited for the last win
+    pub last_reward_timestamp: u64,
+}
+
+impl RewardBalance {
+    pub fn new(chain_id: ChainId) -> Self {
+        Self {
+            chain_id,
+            balance: 0,
+            wins_rewarded: 0,
+            day: 0,
+            earned_today: 0,
+            last_reward: 0,
+            last_reward_timestamp: 0,
+        }
+    }
+    
+    /// Balance as seen at `now`: points earned on an earlier day no longer count towards
+    /// today's cap
+    pub fn current(&self, now: u64, config: &RewardConfig) -> Self {
+        let day = config.day(now);
+        if day == self.day {
+            return self.clone();
+        }
+        Self {
+            day,
+            earned_today: 0,
+            ..self.clone()
+        }
+    }
+    
+    /// Credit a win that brought the streak to `streak`, up to what is left of the daily
+    /// cap. Returns the points credited.
+    pub fn credit_win(&mut self, streak: u64, now: u64, config: &RewardConfig) -> u64 {
+        *self = self.current(now, config);
+        
+        let mut points = config.points_for_win(streak);
+        if let Some(cap) = config.daily_cap {
+            points = points.min(cap.saturating_sub(self.earned_today));
+        }
+        
+        self.balance = self.balance.saturating_add(points);
+        self.earned_today = self.earned_today.saturating_add(points);
+        self.wins_rewarded += 1;
+        self.last_reward = points;
+        self.last_reward_timestamp = now;
+        points
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_utils::chain;
+    
+    /// Microseconds in a default reward day
+    const DAY: u64 = 24 * 60 * 60 * 1_000_000;
+    
+    #[test]
+    fn streak_multiplier() {
+        let config = RewardConfig::default();
+        let expected = [(0, 100, 10), (2, 100, 10), (3, 150, 15), (4, 150, 15), (5, 200, 20), (9, 200, 20), (10, 300, 30), (1000, 300, 30)];
+        for (streak, multiplier, points) in expected {
+            assert_eq!(config.multiplier_percent(streak), multiplier, "{}", streak);
+            assert_eq!(config.points_for_win(streak), points, "{}", streak);
+        }
+        
+        // The highest milestone reached counts, in whatever order the bonuses are listed
+        let config = RewardConfig {
+            base_points: 7,
+            streak_bonuses: vec![
+                StreakBonus { streak: 4, multiplier_percent: 250 },
+                StreakBonus { streak: 2, multiplier_percent: 120 },
+            ],
+            ..RewardConfig::default()
+        };
+        assert_eq!(config.multiplier_percent(3), 120);
+        assert_eq!(config.multiplier_percent(4), 250);
+        assert_eq!(config.points_for_win(2), 8); // Rounded down
+        assert_eq!(config.points_for_win(4), 17);
+        
+        let config = RewardConfig { streak_bonuses: Vec::new(), base_points: u64::MAX, ..RewardConfig::default() };
+        assert_eq!(config.multiplier_percent(50), 100);
+        assert_eq!(config.points_for_win(50), u64::MAX / 100);
+    }
+    
+    #[test]
+    fn daily_cap() {
+        let config = RewardConfig::default();
+        let mut balance = RewardBalance::new(chain(1));
+        for win in 0..16 {
+            assert_eq!(balance.credit_win(10, win, &config), 30);
+        }
+        assert_eq!(balance.credit_win(10, 16, &config), 20);
+        assert_eq!(balance.credit_win(10, 17, &config), 0);
+        assert_eq!(balance.balance, 500);
+        assert_eq!(balance.earned_today, 500);
+        assert_eq!(balance.wins_rewarded, 18);
+        assert_eq!(balance.last_reward, 0);
+        
+        let config = RewardConfig { daily_cap: None, ..RewardConfig::default() };
+        let mut balance = RewardBalance::new(chain(1));
+        for win in 0..100 {
+            assert_eq!(balance.credit_win(10, win, &config), 30);
+        }
+        assert_eq!(balance.balance, 3000);
+    }
+    
+    #[test]
+    fn day_rollover() {
+        let config = RewardConfig { daily_cap: Some(20), ..RewardConfig::default() };
+        assert_eq!(config.day(DAY - 1), 0);
+        assert_eq!(config.day(DAY), 1);
+        assert_eq!(RewardConfig { day_secs: 0, ..RewardConfig::default() }.day(2_500_000), 2);
+        
+        let mut balance = RewardBalance::new(chain(1));
+        assert_eq!(balance.credit_win(1, 0, &config), 10);
+        assert_eq!(balance.credit_win(1, DAY - 1, &config), 10);
+        assert_eq!(balance.credit_win(1, DAY - 1, &config), 0);
+        
+        // A new day reads as uncapped before any credit, without changing the stored balance
+        let current = balance.current(DAY, &config);
+        assert_eq!((current.day, current.earned_today, current.balance), (1, 0, 20));
+        assert_eq!((balance.day, balance.earned_today), (0, 20));
+        assert_eq!(balance.current(DAY - 1, &config).earned_today, 20);
+        
+        assert_eq!(balance.credit_win(1, DAY, &config), 10);
+        assert_eq!((balance.day, balance.earned_today, balance.balance), (1, 10, 30));
+        assert_eq!(balance.last_reward_timestamp, DAY);
+        
+        // Skipping days resets the cap just the same
+        assert_eq!(balance.credit_win(1, 5 * DAY, &config), 10);
+        assert_eq!((balance.day, balance.earned_today, balance.balance), (5, 10, 40));
+    }
+}
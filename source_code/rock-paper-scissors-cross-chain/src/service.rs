@@ -7,16 +7,22 @@ mod state;
 
 use std::sync::Arc;
 
-use async_graphql::{ComplexObject, EmptySubscription, Object, Request, Response, Schema};
-use linera_sdk::{linera_base_types::WithServiceAbi, views::View, Service, ServiceRuntime};
-use rock_paper_scissors::{RockPaperScissorsAbi, GameRoom, LeaderboardEntry, Choice};
+use async_graphql::connection::{Connection, Edge};
+use async_graphql::{ComplexObject, Context, EmptySubscription, Object, OutputType, Request, Response, Schema};
+use linera_sdk::{linera_base_types::{Amount, ApplicationId, ChainId, WithServiceAbi}, views::View, Service, ServiceRuntime};
+use rock_paper_scissors::{ApplicationParameters, RockPaperScissorsAbi, GameRoom, LeaderboardEntry, Choice, Currency, MatchFormat, RejectedAction, RoomFilter, RoomOrder, RoomStatus, TimeoutPenalty, LeaderboardOrder, QueueEntry,
+    Series,     validate_player_name, normalize_room_code};
+use rock_paper_scissors::glicko2::Glicko2Rating;
+use rock_paper_scissors::league::{League, LeagueFormat, LeagueStanding};
+use rock_paper_scissors::rewards::{RewardBalance, RewardConfig};
+use rock_paper_scissors::tournament::{SeedingMethod, Tournament, TournamentFormat};
 
-use self::state::{RockPaperScissorsState, PlayerStats};
+use self::state::{RockPaperScissorsState, PlayerStats, RatingChange};
 
 linera_sdk::service!(RockPaperScissorsService);
 
 pub struct RockPaperScissorsService {
-    state: RockPaperScissorsState,
+    state: Arc<RockPaperScissorsState>,
     runtime: Arc<ServiceRuntime<Self>>,
 }
 
@@ -25,223 +31,641 @@ impl WithServiceAbi for RockPaperScissorsService {
 }
 
 impl Service for RockPaperScissorsService {
-    type Parameters = ();
+    type Parameters = ApplicationParameters;
 
     async fn new(runtime: ServiceRuntime<Self>) -> Self {
         let state = RockPaperScissorsState::load(runtime.root_view_storage_context())
             .await
             .expect("Failed to load state");
         RockPaperScissorsService {
-            state,
+            state: Arc::new(state),
             runtime: Arc::new(runtime),
         }
     }
 
     async fn handle_query(&self, request: Request) -> Response {
-        // Collect available rooms
-        let mut available_rooms = Vec::new();
-        let available_room_ids = self.state.available_rooms.get().clone();
-        for room_id in available_room_ids {
-            if let Ok(Some(room)) = self.state.rooms.get(&room_id).await {
-                if !room.is_full() && !room.game_result.is_finished {
-                    available_rooms.push(room);
-                }
-            }
-        }
-        
-        // Collect all rooms
-        let mut all_rooms = Vec::new();
-        if let Ok(room_ids) = self.state.rooms.indices().await {
-            for room_id in room_ids {
-                if let Ok(Some(room)) = self.state.rooms.get(&room_id).await {
-                    all_rooms.push(room);
-                }
-            }
-        }
-        
-        // Get leaderboard data
-        let global_leaderboard = self.state.global_leaderboard.get().clone();
-        
-        // Get player stats
-        let mut all_player_stats = Vec::new();
-        if let Ok(player_chains) = self.state.player_stats.indices().await {
-            for player_chain in player_chains {
-                if let Ok(Some(stats)) = self.state.player_stats.get(&player_chain).await {
-                    all_player_stats.push(stats);
-                }
-            }
-        }
-        
-        // Get personal data
-        let my_rooms = self.state.my_rooms.get().clone();
-        let my_stats = self.state.my_stats.get().clone();
-        let my_current_room = self.state.my_current_room.get().clone();
-        let my_player_name = self.state.my_player_name.get().clone();
-        
-        // Get all player names
-        let mut all_player_names = Vec::new();
-        if let Ok(chain_ids) = self.state.player_names.indices().await {
-            for chain_id in chain_ids {
-                if let Ok(Some(name)) = self.state.player_names.get(&chain_id).await {
-                    all_player_names.push(PlayerNameEntry {
-                        chain_id: chain_id.to_string(),
-                        name,
-                    });
-                }
-            }
-        }
-        
-        // Get configuration
-        let is_leaderboard_chain = *self.state.is_leaderboard_chain.get();
-        let leaderboard_chain_id = self.state.leaderboard_chain_id.get().clone();
-        let room_counter = *self.state.room_counter.get();
-        
         let schema = Schema::build(
             QueryRoot {
-                available_rooms,
-                all_rooms,
-                global_leaderboard,
-                all_player_stats,
-                my_rooms,
-                my_stats,
-                my_current_room,
-                is_leaderboard_chain,
-                leaderboard_chain_id,
-                room_counter,
-                my_player_name,
-                all_player_names,
+                state: self.state.clone(),
+                runtime: self.runtime.clone(),
             },
             MutationRoot {
                 runtime: self.runtime.clone(),
             },
             EmptySubscription,
         )
+        .data(self.state.clone())
         .finish();
         
         schema.execute(request).await
     }
 }
 
+/// Page size of paginated lists when `first` is not given
+const DEFAULT_PAGE_SIZE: usize = 100;
+/// Largest page a single query can ask for
+const MAX_PAGE_SIZE: usize = 1000;
+
+/// Picks the indices of one page while walking the indices of a map in storage order, which
+/// must agree with the order of `K`. A page starts at the first index after the `after`
+/// cursor, so a cursor whose item is gone by the next page still resumes in place.
+struct Page<K> {
+    after: Option<K>,
+    first: usize,
+    indices: Vec<K>,
+    has_previous: bool,
+    has_next: bool,
+}
+
+impl<K: Ord> Page<K> {
+    fn new(first: Option<i32>, after: Option<K>) -> Self {
+        let first = first.map_or(DEFAULT_PAGE_SIZE, |first| first.clamp(0, MAX_PAGE_SIZE as i32) as usize);
+        Self {
+            after,
+            first,
+            indices: Vec::new(),
+            has_previous: false,
+            has_next: false,
+        }
+    }
+    
+    /// Visit the next index, returning whether the walk should go on
+    fn visit(&mut self, index: K) -> bool {
+        // Views have no seek, so the indices up to the cursor are walked over
+        if self.after.as_ref().is_some_and(|after| index <= *after) {
+            self.has_previous = true;
+            return true;
+        }
+        if self.indices.len() == self.first {
+            self.has_next = true;
+            return false;
+        }
+        self.indices.push(index);
+        true
+    }
+    
+    /// Connection holding `nodes`, one per collected index, in page order
+    fn into_connection<N: OutputType>(self, nodes: Vec<(String, N)>, total_count: usize) -> Connection<String, N, TotalCount> {
+        let mut connection = Connection::with_additional_fields(self.has_previous, self.has_next, TotalCount {
+            total_count: total_count as u64,
+        });
+        connection.edges.extend(nodes.into_iter().map(|(cursor, node)| Edge::new(cursor, node)));
+        connection
+    }
+}
+
+/// Number of items across all pages of a list
+#[derive(async_graphql::SimpleObject)]
+struct TotalCount {
+    total_count: u64,
+}
+
+/// Index of a `String` key in storage order: keys are stored with their length first
+fn storage_key(key: String) -> (usize, String) {
+    (key.len(), key)
+}
+
+/// Check that a cursor of a chain-keyed list is a chain ID
+fn chain_cursor(after: Option<String>) -> async_graphql::Result<Option<String>> {
+    if let Some(cursor) = &after {
+        cursor.parse::<ChainId>().map_err(|_| async_graphql::Error::new(format!("Malformed cursor {cursor}")))?;
+    }
+    Ok(after)
+}
+
+/// Cursor of a room in a room search, stable while the room's status changes
+fn room_cursor(room: &GameRoom) -> String {
+    format!("{}:{}", room.created_at, room.room_id)
+}
+
+/// Creation time and room ID held by a room search cursor
+fn parse_room_cursor(cursor: &str) -> async_graphql::Result<(u64, &str)> {
+    cursor
+        .split_once(':')
+        .and_then(|(created_at, room_id)| Some((created_at.parse().ok()?, room_id)))
+        .ok_or_else(|| async_graphql::Error::new(format!("Malformed cursor {cursor}")))
+}
+
+/// Longest stretch of neighbours `leaderboardAround` returns on each side of a player
+const MAX_LEADERBOARD_RADIUS: usize = 50;
+
+/// A player's place on the global leaderboard
+#[derive(async_graphql::SimpleObject)]
+struct LeaderboardPosition {
+    rank: u64, // 1-based
+    total_players: u64,
+    percentile: f64, // Share of the other players ranked below, 100 for the best player
+}
+
+impl LeaderboardPosition {
+    fn new(rank: u64, total_players: u64) -> Self {
+        Self {
+            rank,
+            total_players,
+            percentile: percentile(rank, total_players),
+        }
+    }
+    
+    /// Position of `player` in `order`, None when they are not on the leaderboard
+    async fn of(state: &RockPaperScissorsState, order: LeaderboardOrder, player: ChainId) -> async_graphql::Result<Option<Self>> {
+        let rank = state.leaderboard_ranks(order, &[player]).await?[0];
+        let total_players = *state.leaderboard_count.get();
+        Ok(rank.map(|rank| Self::new(rank, total_players)))
+    }
+}
+
+/// Percentage of the other `total_players - 1` players that `rank` is ahead of
+fn percentile(rank: u64, total_players: u64) -> f64 {
+    if total_players <= 1 {
+        return 100.0;
+    }
+    100.0 * total_players.saturating_sub(rank) as f64 / (total_players - 1) as f64
+}
+
+#[derive(async_graphql::SimpleObject)]
+struct RankedLeaderboardEntry {
+    rank: u64, // 1-based
+    entry: LeaderboardEntry,
+}
+
 struct QueryRoot {
-    available_rooms: Vec<GameRoom>,
-    all_rooms: Vec<GameRoom>,
-    global_leaderboard: Vec<LeaderboardEntry>,
-    all_player_stats: Vec<PlayerStats>,
-    my_rooms: Vec<String>,
-    my_stats: Option<PlayerStats>,
-    my_current_room: Option<String>,
-    is_leaderboard_chain: bool,
-    leaderboard_chain_id: Option<linera_sdk::linera_base_types::ChainId>,
-    room_counter: u64,
-    my_player_name: Option<String>,
-    all_player_names: Vec<PlayerNameEntry>,
+    state: Arc<RockPaperScissorsState>,
+    runtime: Arc<ServiceRuntime<RockPaperScissorsService>>,
+}
+
+impl QueryRoot {
+    /// Open rooms in any of `statuses`, read through the open room index
+    async fn open_rooms(&self, statuses: &[RoomStatus]) -> async_graphql::Result<Vec<GameRoom>> {
+        let mut room_ids = Vec::new();
+        self.state.open_rooms.for_each_index_value(|room_id, status| {
+            if statuses.contains(&*status) {
+                room_ids.push(room_id);
+            }
+            Ok(())
+        }).await?;
+        
+        let mut rooms = Vec::new();
+        for room_id in room_ids {
+            if let Some(room) = self.state.rooms.get(&room_id).await? {
+                rooms.push(room);
+            }
+        }
+        Ok(rooms)
+    }
+    
+    /// Rooms waiting for an opponent
+    async fn waiting_rooms(&self) -> async_graphql::Result<Vec<GameRoom>> {
+        let mut rooms = Vec::new();
+        for room_id in self.state.available_rooms.get() {
+            if let Some(room) = self.state.rooms.get(room_id).await? {
+                if room.status == RoomStatus::WaitingForOpponent {
+                    rooms.push(room);
+                }
+            }
+        }
+        Ok(rooms)
+    }
+    
+    /// Look up a room by code, loosely typed code or alias
+    async fn find_room(&self, room_id: &str) -> async_graphql::Result<Option<GameRoom>> {
+        if let Some(room) = self.state.rooms.get(room_id).await? {
+            return Ok(Some(room));
+        }
+        if let Some(code) = normalize_room_code(room_id) {
+            if let Some(room) = self.state.rooms.get(&code).await? {
+                return Ok(Some(room));
+            }
+        }
+        match self.state.room_aliases.get(room_id).await? {
+            Some(code) => Ok(self.state.rooms.get(&code).await?),
+            None => Ok(None),
+        }
+    }
+    
+    fn now(&self) -> u64 {
+        self.runtime.system_time().micros()
+    }
 }
 
 #[Object]
 impl QueryRoot {
     /// Get all available rooms (waiting for players)
-    async fn available_rooms(&self) -> &Vec<GameRoom> {
-        &self.available_rooms
+    async fn available_rooms(&self) -> async_graphql::Result<Vec<GameRoom>> {
+        self.waiting_rooms().await
     }
     
     /// Get all public rooms (waiting for players)
-    async fn public_rooms(&self) -> Vec<&GameRoom> {
-        self.available_rooms.iter().filter(|room| !room.private).collect()
+    async fn public_rooms(&self) -> async_graphql::Result<Vec<GameRoom>> {
+        Ok(self.waiting_rooms().await?.into_iter().filter(|room| !room.private).collect())
     }
     
     /// Get all private rooms (waiting for players)
-    async fn private_rooms(&self) -> Vec<&GameRoom> {
-        self.available_rooms.iter().filter(|room| room.private).collect()
+    async fn private_rooms(&self) -> async_graphql::Result<Vec<GameRoom>> {
+        Ok(self.waiting_rooms().await?.into_iter().filter(|room| room.private).collect())
+    }
+    
+    /// Get all rooms (including finished games), `first` at a time after the `after` cursor
+    async fn all_rooms(&self, first: Option<i32>, after: Option<String>) -> async_graphql::Result<Connection<String, GameRoom, TotalCount>> {
+        let mut page = Page::new(first, after.map(storage_key));
+        self.state.rooms.for_each_index_while(|room_id| Ok(page.visit(storage_key(room_id)))).await?;
+        
+        let mut rooms = Vec::new();
+        for (_, room_id) in &page.indices {
+            if let Some(room) = self.state.rooms.get(room_id).await? {
+                rooms.push((room_id.clone(), room));
+            }
+        }
+        let total_count = self.state.rooms.count().await?;
+        Ok(page.into_connection(rooms, total_count))
+    }
+    
+    /// Search rooms matching `filter`, sorted by `orderBy` (newest first by default), `first`
+    /// rooms at a time after the `after` cursor
+    async fn rooms(
+        &self,
+        filter: Option<RoomFilter>,
+        order_by: Option<RoomOrder>,
+        first: Option<i32>,
+        after: Option<String>,
+    ) -> async_graphql::Result<Connection<String, GameRoom, TotalCount>> {
+        let filter = filter.unwrap_or_default();
+        let mut rooms = Vec::new();
+        match filter.open_statuses() {
+            Some(statuses) => {
+                for room in self.open_rooms(statuses).await? {
+                    if filter.matches(&room) {
+                        rooms.push(room);
+                    }
+                }
+            }
+            None => {
+                self.state.rooms.for_each_index_value(|_, room| {
+                    if filter.matches(&room) {
+                        rooms.push(room.into_owned());
+                    }
+                    Ok(())
+                }).await?;
+            }
+        }
+        let order = order_by.unwrap_or_default();
+        order.sort(&mut rooms);
+        
+        let total_count = rooms.len();
+        let start = match &after {
+            Some(cursor) => order.resume_index(&rooms, parse_room_cursor(cursor)?),
+            None => 0,
+        };
+        let mut page = Page::new(first, None);
+        page.has_previous = start > 0;
+        let mut nodes = Vec::new();
+        for room in rooms.into_iter().skip(start) {
+            let cursor = room_cursor(&room);
+            if !page.visit(cursor.clone()) {
+                break;
+            }
+            nodes.push((cursor, room));
+        }
+        Ok(page.into_connection(nodes, total_count))
+    }
+    
+    /// Get all rooms with the given status
+    async fn rooms_by_status(&self, status: RoomStatus) -> async_graphql::Result<Vec<GameRoom>> {
+        if !status.is_closed() {
+            return self.open_rooms(&[status]).await;
+        }
+        let mut rooms = Vec::new();
+        self.state.rooms.for_each_index_value(|_, room| {
+            if room.status == status {
+                rooms.push(room.into_owned());
+            }
+            Ok(())
+        }).await?;
+        Ok(rooms)
+    }
+    
+    /// Get a specific room by code (loosely typed codes are accepted) or alias
+    async fn room(&self, room_id: String) -> async_graphql::Result<Option<GameRoom>> {
+        self.find_room(&room_id).await
+    }
+    
+    /// Get every room in the rematch chain of a room, oldest first
+    async fn room_series(&self, room_id: String) -> async_graphql::Result<Vec<GameRoom>> {
+        let Some(mut first) = self.find_room(&room_id).await? else {
+            return Ok(Vec::new());
+        };
+        while let Some(previous) = first.previous_room.as_deref() {
+            match self.state.rooms.get(previous).await? {
+                Some(room) => first = room,
+                None => break,
+            }
+        }
+        
+        let mut series = vec![first];
+        while let Some(next) = series.last().and_then(|room| room.next_room.clone()) {
+            match self.state.rooms.get(&next).await? {
+                Some(room) => series.push(room),
+                None => break,
+            }
+        }
+        Ok(series)
+    }
+    
+    /// Get all best-of-N series, oldest first
+    async fn all_series(&self) -> async_graphql::Result<Vec<Series>> {
+        let mut all_series = Vec::new();
+        self.state.series.for_each_index_value(|_, series| {
+            all_series.push(series.into_owned());
+            Ok(())
+        }).await?;
+        all_series.sort_by_key(|series| series.created_at);
+        Ok(all_series)
     }
     
-    /// Get all rooms (including finished games)
-    async fn all_rooms(&self) -> &Vec<GameRoom> {
-        &self.all_rooms
+    /// Get a series by ID
+    async fn series(&self, series_id: String) -> async_graphql::Result<Option<Series>> {
+        Ok(self.state.series.get(&series_id).await?)
     }
     
-    /// Get a specific room by ID
-    async fn room(&self, room_id: String) -> Option<&GameRoom> {
-        self.all_rooms.iter().find(|room| room.room_id == room_id)
+    /// Get all tournaments, oldest first
+    async fn tournaments(&self) -> async_graphql::Result<Vec<Tournament>> {
+        let mut tournaments = Vec::new();
+        self.state.tournaments.for_each_index_value(|_, tournament| {
+            tournaments.push(tournament.into_owned());
+            Ok(())
+        }).await?;
+        tournaments.sort_by_key(|tournament| tournament.created_at);
+        Ok(tournaments)
     }
     
-    /// Get the global leaderboard
-    async fn global_leaderboard(&self) -> &Vec<LeaderboardEntry> {
-        &self.global_leaderboard
+    /// Get a tournament by ID; its `bracket` field lists the matches round by round
+    async fn tournament(&self, tournament_id: String) -> async_graphql::Result<Option<Tournament>> {
+        Ok(self.state.tournaments.get(&tournament_id).await?)
     }
     
-    /// Get all player statistics
-    async fn all_player_stats(&self) -> &Vec<PlayerStats> {
-        &self.all_player_stats
+    /// Get all leagues, oldest first
+    async fn leagues(&self) -> async_graphql::Result<Vec<League>> {
+        let mut leagues = Vec::new();
+        self.state.leagues.for_each_index_value(|_, league| {
+            leagues.push(league.into_owned());
+            Ok(())
+        }).await?;
+        leagues.sort_by_key(|league| league.created_at);
+        Ok(leagues)
+    }
+    
+    /// Get a league by ID
+    async fn league(&self, league_id: String) -> async_graphql::Result<Option<League>> {
+        Ok(self.state.leagues.get(&league_id).await?)
+    }
+    
+    /// Get the standings table of a league, best player first
+    async fn league_standings(&self, league_id: String) -> async_graphql::Result<Vec<LeagueStanding>> {
+        Ok(self.state.leagues.get(&league_id).await?
+            .map(|league| league.compute_standings())
+            .unwrap_or_default())
+    }
+    
+    /// Get the global leaderboard, ordered by wins unless another order is given, `first`
+    /// players at a time after the `after` cursor
+    async fn global_leaderboard(
+        &self,
+        order_by: Option<LeaderboardOrder>,
+        first: Option<i32>,
+        after: Option<String>,
+    ) -> async_graphql::Result<Connection<String, LeaderboardEntry, TotalCount>> {
+        let (index, rank_key): (_, fn(&LeaderboardEntry) -> String) = match order_by.unwrap_or_default() {
+            LeaderboardOrder::Wins => (&self.state.leaderboard_by_wins, LeaderboardEntry::wins_rank_key),
+            LeaderboardOrder::Rating => (&self.state.leaderboard_by_rating, LeaderboardEntry::rating_rank_key),
+        };
+        // The cursor is the player's chain, and the page resumes after wherever it ranks now
+        let after = match chain_cursor(after)? {
+            Some(cursor) => match self.state.leaderboard_entries.get(&cursor.parse()?).await? {
+                Some(entry) => Some(rank_key(&entry)),
+                None => return Err(async_graphql::Error::new(format!("Unknown cursor {cursor}"))),
+            },
+            None => None,
+        };
+        let mut page = Page::new(first, after);
+        index.for_each_index_while(|rank_key| Ok(page.visit(rank_key))).await?;
+        
+        let mut entries = Vec::new();
+        for rank_key in &page.indices {
+            if let Some(mut entry) = index.get(rank_key).await? {
+                entry.player_name = self.state.player_names.get(&entry.chain_id).await?;
+                entries.push((entry.chain_id.to_string(), entry));
+            }
+        }
+        let total_count = *self.state.leaderboard_count.get() as usize;
+        Ok(page.into_connection(entries, total_count))
+    }
+    
+    /// Get the recent Elo rating changes of a player, oldest first
+    async fn rating_history(&self, chain_id: ChainId) -> async_graphql::Result<Vec<RatingChange>> {
+        Ok(self.state.rating_history.get(&chain_id).await?.unwrap_or_default())
+    }
+    
+    /// Get all player statistics, `first` players at a time after the `after` cursor
+    async fn all_player_stats(&self, first: Option<i32>, after: Option<String>) -> async_graphql::Result<Connection<String, PlayerStats, TotalCount>> {
+        let mut page = Page::new(first, chain_cursor(after)?);
+        self.state.player_stats.for_each_index_while(|player_chain| Ok(page.visit(player_chain.to_string()))).await?;
+        
+        let mut all_stats = Vec::new();
+        for cursor in &page.indices {
+            let player_chain: ChainId = cursor.parse()?;
+            if let Some(stats) = self.state.player_stats.get(&player_chain).await? {
+                all_stats.push((cursor.clone(), stats));
+            }
+        }
+        let total_count = self.state.player_stats.count().await?;
+        Ok(page.into_connection(all_stats, total_count))
     }
     
     /// Get player statistics for a specific chain
-    async fn player_stats(&self, chain_id: String) -> Option<&PlayerStats> {
-        // Parse chain_id string to ChainId if needed
-        self.all_player_stats.iter().find(|stats| {
-            format!("{:?}", stats.chain_id).contains(&chain_id)
-        })
+    async fn player_stats(&self, chain_id: ChainId) -> async_graphql::Result<Option<PlayerStats>> {
+        Ok(self.state.player_stats.get(&chain_id).await?)
+    }
+    
+    /// Get a player's rank, the number of ranked players and their percentile on the global
+    /// leaderboard, by wins unless another order is given
+    async fn player_rank(&self, chain_id: ChainId, order_by: Option<LeaderboardOrder>) -> async_graphql::Result<Option<LeaderboardPosition>> {
+        LeaderboardPosition::of(&self.state, order_by.unwrap_or_default(), chain_id).await
+    }
+    
+    /// Get up to `radius` players ranked right above and below a player, the player included,
+    /// best first; by wins unless another order is given
+    async fn leaderboard_around(
+        &self,
+        chain_id: ChainId,
+        radius: Option<i32>,
+        order_by: Option<LeaderboardOrder>,
+    ) -> async_graphql::Result<Vec<RankedLeaderboardEntry>> {
+        let radius = radius.map_or(5, |radius| radius.clamp(0, MAX_LEADERBOARD_RADIUS as i32) as usize);
+        Ok(self.state.leaderboard_window(order_by.unwrap_or_default(), chain_id, radius).await?
+            .into_iter()
+            .map(|(rank, entry)| RankedLeaderboardEntry { rank, entry })
+            .collect())
+    }
+    
+    /// Get the stats, name and leaderboard rank of several players at once, in the order
+    /// asked; ranks follow `orderBy`, by wins unless another order is given
+    async fn players(&self, chain_ids: Vec<ChainId>, order_by: Option<LeaderboardOrder>) -> async_graphql::Result<Vec<PlayerProfile>> {
+        let ranks = self.state.leaderboard_ranks(order_by.unwrap_or_default(), &chain_ids).await?;
+        let total_players = *self.state.leaderboard_count.get();
+        let mut players = Vec::new();
+        for (chain_id, rank) in chain_ids.into_iter().zip(ranks) {
+            players.push(PlayerProfile {
+                chain_id,
+                name: self.state.player_names.get(&chain_id).await?,
+                stats: self.state.player_stats.get(&chain_id).await?,
+                rank,
+                percentile: rank.map(|rank| percentile(rank, total_players)),
+            });
+        }
+        Ok(players)
+    }
+    
+    /// Get a player's Glicko-2 rating as of now, with deviation grown for inactivity and
+    /// its 95% confidence interval
+    async fn glicko2_rating(&self, chain_id: ChainId) -> async_graphql::Result<Option<Glicko2Rating>> {
+        let config = self.runtime.application_parameters().glicko2;
+        Ok(self.state.player_stats.get(&chain_id).await?
+            .map(|stats| stats.current_glicko2(self.now(), &config)))
+    }
+    
+    /// Reward points a player earned for public wins, with today's total as of now
+    async fn reward_balance(&self, chain_id: ChainId) -> async_graphql::Result<Option<RewardBalance>> {
+        let config = self.runtime.application_parameters().rewards;
+        Ok(self.state.reward_balances.get(&chain_id).await?
+            .map(|balance| balance.current(self.now(), &config)))
+    }
+    
+    /// Reward balances of all players, highest balance first
+    async fn reward_balances(&self) -> async_graphql::Result<Vec<RewardBalance>> {
+        let config = self.runtime.application_parameters().rewards;
+        let now = self.now();
+        let mut balances = Vec::new();
+        self.state.reward_balances.for_each_index_value(|_, balance| {
+            balances.push(balance.current(now, &config));
+            Ok(())
+        }).await?;
+        balances.sort_by_key(|balance| std::cmp::Reverse(balance.balance));
+        Ok(balances)
+    }
+    
+    /// Points per win, streak multipliers and daily cap of the reward system
+    async fn reward_config(&self) -> RewardConfig {
+        self.runtime.application_parameters().rewards
     }
     
     /// Get rooms this player is participating in
     async fn my_rooms(&self) -> &Vec<String> {
-        &self.my_rooms
+        self.state.my_rooms.get()
     }
     
     /// Get personal statistics
     async fn my_stats(&self) -> &Option<PlayerStats> {
-        &self.my_stats
+        self.state.my_stats.get()
     }
     
     /// Get current active room
     async fn my_current_room(&self) -> &Option<String> {
-        &self.my_current_room
+        self.state.my_current_room.get()
     }
     
     /// Check if this chain is the leaderboard chain
     async fn is_leaderboard_chain(&self) -> bool {
-        self.is_leaderboard_chain
+        *self.state.is_leaderboard_chain.get()
     }
     
     /// Get the configured leaderboard chain ID
     async fn leaderboard_chain_id(&self) -> Option<String> {
-        self.leaderboard_chain_id.map(|id| id.to_string())
+        self.state.leaderboard_chain_id.get().map(|id| id.to_string())
+    }
+    
+    /// Fungible token application that `Token` entry fees are paid in, if one is configured
+    async fn fungible_token(&self) -> Option<ApplicationId> {
+        self.runtime.application_parameters().fungible_token.map(ApplicationId::forget_abi)
     }
     
     /// Get the current room counter
     async fn room_counter(&self) -> u64 {
-        self.room_counter
+        *self.state.room_counter.get()
     }
     
     /// Get my player name
     async fn my_player_name(&self) -> &Option<String> {
-        &self.my_player_name
+        self.state.my_player_name.get()
     }
     
-    /// Get all player names
-    async fn all_player_names(&self) -> &Vec<PlayerNameEntry> {
-        &self.all_player_names
+    /// Get the last request from this chain that the leaderboard chain rejected
+    async fn my_last_error(&self) -> &Option<RejectedAction> {
+        self.state.my_last_error.get()
+    }
+    
+    /// Check whether this player is waiting for a quick match
+    async fn my_queued(&self) -> bool {
+        *self.state.my_queued.get()
+    }
+    
+    /// Get players waiting for a quick match, longest waiting first (leaderboard chain only)
+    async fn matchmaking_queue(&self) -> async_graphql::Result<Vec<QueueEntry>> {
+        let mut queue = Vec::new();
+        self.state.matchmaking_queue.for_each_index_value(|_, entry| {
+            queue.push(entry.into_owned());
+            Ok(())
+        }).await?;
+        queue.sort_by_key(|entry| entry.queued_at);
+        Ok(queue)
+    }
+    
+    /// Get all player names, `first` players at a time after the `after` cursor
+    async fn all_player_names(&self, first: Option<i32>, after: Option<String>) -> async_graphql::Result<Connection<String, PlayerNameEntry, TotalCount>> {
+        let mut page = Page::new(first, chain_cursor(after)?);
+        self.state.player_names.for_each_index_while(|player_chain| Ok(page.visit(player_chain.to_string()))).await?;
+        
+        let mut names = Vec::new();
+        for cursor in &page.indices {
+            let player_chain: ChainId = cursor.parse()?;
+            if let Some(name) = self.state.player_names.get(&player_chain).await? {
+                names.push((cursor.clone(), PlayerNameEntry {
+                    chain_id: player_chain,
+                    name,
+                }));
+            }
+        }
+        let total_count = self.state.player_names.count().await?;
+        Ok(page.into_connection(names, total_count))
     }
     
     /// Get player name by chain ID
-    async fn player_name(&self, chain_id: String) -> Option<String> {
-        self.all_player_names.iter()
-            .find(|entry| entry.chain_id == chain_id)
-            .map(|entry| entry.name.clone())
+    async fn player_name(&self, chain_id: ChainId) -> async_graphql::Result<Option<String>> {
+        Ok(self.state.player_names.get(&chain_id).await?)
+    }
+    
+    /// Compute the commitment this chain must submit for a choice (keep the salt secret until revealing)
+    async fn choice_commitment(&self, room_id: String, round_number: u16, choice: Choice, salt: String) -> String {
+        rock_paper_scissors::choice_commitment(&room_id, round_number, self.runtime.chain_id(), choice, &salt)
     }
     
     /// Get game statistics summary
-    async fn game_stats(&self) -> GameStats {
-        let total_rooms = self.all_rooms.len() as u64;
-        let active_rooms = self.available_rooms.len() as u64;
-        let finished_games = self.all_rooms.iter().filter(|room| room.game_result.is_finished).count() as u64;
-        let total_players = self.all_player_stats.len() as u64;
+    async fn game_stats(&self) -> async_graphql::Result<GameStats> {
+        let total_rooms = self.state.rooms.count().await? as u64;
+        let active_rooms = self.waiting_rooms().await?.len() as u64;
+        let mut finished_games = 0;
+        self.state.rooms.for_each_index_value(|_, room| {
+            if room.status == RoomStatus::Finished {
+                finished_games += 1;
+            }
+            Ok(())
+        }).await?;
+        let total_players = self.state.player_stats.count().await? as u64;
         
-        GameStats {
+        Ok(GameStats {
             total_rooms,
             active_rooms,
             finished_games,
             total_players,
-        }
+        })
     }
 }
 
@@ -255,44 +679,255 @@ struct GameStats {
 
 #[derive(async_graphql::SimpleObject)]
 struct PlayerNameEntry {
-    chain_id: String,
+    chain_id: ChainId,
     name: String,
 }
 
+#[derive(async_graphql::SimpleObject)]
+struct PlayerProfile {
+    chain_id: ChainId,
+    name: Option<String>,
+    stats: Option<PlayerStats>, // None before the first public game
+    rank: Option<u64>, // 1-based leaderboard rank, None before the first public game
+    percentile: Option<f64>,
+}
+
 struct MutationRoot {
     runtime: Arc<ServiceRuntime<RockPaperScissorsService>>,
 }
 
 #[Object]
+#[allow(clippy::too_many_arguments)]
 impl MutationRoot {
     /// Setup the leaderboard chain (admin operation)
-    async fn setup_leaderboard(&self, leaderboard_chain_id: String) -> String {
-        // Parse chain ID string
-        let chain_id = match leaderboard_chain_id.parse() {
-            Ok(id) => id,
-            Err(_) => return format!("Invalid chain ID format: {}", leaderboard_chain_id),
-        };
-        
-        self.runtime.schedule_operation(&rock_paper_scissors::Operation::SetupLeaderboard { leaderboard_chain_id: chain_id });
+    async fn setup_leaderboard(&self, leaderboard_chain_id: ChainId) -> String {
+        self.runtime.schedule_operation(&rock_paper_scissors::Operation::SetupLeaderboard { leaderboard_chain_id });
         format!("Setup leaderboard with chain ID: {}", leaderboard_chain_id)
     }
     
-    /// Create a new room (only on leaderboard chain)
-    async fn create_room(&self, room_id: String, private: bool) -> String {
-        self.runtime.schedule_operation(&rock_paper_scissors::Operation::CreateRoom { room_id: room_id.clone(), private });
-        format!("New {} game room '{}' created successfully", if private { "private" } else { "public" }, room_id)
+    /// Create a new room; from a player chain this chain hosts it and takes the first seat.
+    /// The leaderboard chain allocates the room code, `alias` is an optional vanity name.
+    /// Private rooms can be limited to `allowedPlayers` and/or require `inviteCode`.
+    /// With an `entryFee` every player, the host included, pays it into escrow to take a seat,
+    /// in native tokens or, with `currency: TOKEN`, in the configured fungible token.
+    async fn create_room(
+        &self,
+        alias: Option<String>,
+        private: bool,
+        allowed_players: Option<Vec<ChainId>>,
+        invite_code: Option<String>,
+        format: Option<MatchFormat>,
+        move_timeout_secs: Option<u64>,
+        timeout_penalty: Option<TimeoutPenalty>,
+        entry_fee: Option<Amount>,
+        currency: Option<Currency>,
+    ) -> String {
+        let visibility = if private { "private" } else { "public" };
+        let message = match &alias {
+            Some(alias) => format!("New {} game room '{}' requested", visibility, alias),
+            None => format!("New {} game room requested", visibility),
+        };
+        self.runtime.schedule_operation(&rock_paper_scissors::Operation::CreateRoom {
+            alias,
+            private,
+            allowed_players: allowed_players.unwrap_or_default(),
+            invite_code,
+            format,
+            move_timeout_secs,
+            timeout_penalty,
+            entry_fee,
+            currency,
+        });
+        message
+    }
+    
+    /// Start a best-of-N series between two players (admin operation, only on leaderboard chain).
+    /// Each game gets its own room; the next one opens until a player clinches the series.
+    async fn create_series(
+        &self,
+        player1: ChainId,
+        player2: ChainId,
+        best_of: u8,
+        private: bool,
+        format: Option<MatchFormat>,
+        move_timeout_secs: Option<u64>,
+        timeout_penalty: Option<TimeoutPenalty>,
+    ) -> String {
+        self.runtime.schedule_operation(&rock_paper_scissors::Operation::CreateSeries {
+            player1,
+            player2,
+            best_of,
+            private,
+            format,
+            move_timeout_secs,
+            timeout_penalty,
+        });
+        format!("Best-of-{} series requested", best_of)
+    }
+    
+    /// Create an elimination tournament (admin operation, only on leaderboard chain).
+    /// `players` are registered right away; others register with `registerForTournament`.
+    async fn create_tournament(
+        &self,
+        name: String,
+        format: Option<TournamentFormat>,
+        seeding: Option<SeedingMethod>,
+        max_players: Option<u32>,
+        players: Option<Vec<ChainId>>,
+        private: Option<bool>,
+        match_format: Option<MatchFormat>,
+        move_timeout_secs: Option<u64>,
+        timeout_penalty: Option<TimeoutPenalty>,
+        entry_fee: Option<Amount>,
+        currency: Option<Currency>,
+    ) -> String {
+        let message = format!("Tournament '{}' requested", name);
+        self.runtime.schedule_operation(&rock_paper_scissors::Operation::CreateTournament {
+            name,
+            format: format.unwrap_or_default(),
+            seeding: seeding.unwrap_or_default(),
+            max_players,
+            players: players.unwrap_or_default(),
+            private: private.unwrap_or(false),
+            match_format,
+            move_timeout_secs,
+            timeout_penalty,
+            entry_fee,
+            currency,
+        });
+        message
+    }
+    
+    /// Register this chain for a tournament, paying its entry fee if it has one
+    async fn register_for_tournament(&self, tournament_id: String, entry_fee: Option<Amount>, currency: Option<Currency>) -> String {
+        self.runtime.schedule_operation(&rock_paper_scissors::Operation::RegisterForTournament { tournament_id: tournament_id.clone(), entry_fee, currency });
+        format!("Registration for tournament {} requested", tournament_id)
+    }
+    
+    /// Close registration, seed the bracket and open the first matches (admin operation)
+    async fn start_tournament(&self, tournament_id: String) -> String {
+        self.runtime.schedule_operation(&rock_paper_scissors::Operation::StartTournament { tournament_id: tournament_id.clone() });
+        format!("Start of tournament {} requested", tournament_id)
+    }
+    
+    /// Close a tournament still in registration and refund its entry fees (admin operation)
+    async fn cancel_tournament(&self, tournament_id: String) -> String {
+        self.runtime.schedule_operation(&rock_paper_scissors::Operation::CancelTournament { tournament_id: tournament_id.clone() });
+        format!("Cancellation of tournament {} requested", tournament_id)
+    }
+    
+    /// Create a Swiss or round-robin league (admin operation, only on leaderboard chain).
+    /// `players` are registered right away; others register with `registerForLeague`.
+    async fn create_league(
+        &self,
+        name: String,
+        format: Option<LeagueFormat>,
+        rounds: Option<u32>,
+        max_players: Option<u32>,
+        players: Option<Vec<ChainId>>,
+        private: Option<bool>,
+        match_format: Option<MatchFormat>,
+        move_timeout_secs: Option<u64>,
+        timeout_penalty: Option<TimeoutPenalty>,
+    ) -> String {
+        let message = format!("League '{}' requested", name);
+        self.runtime.schedule_operation(&rock_paper_scissors::Operation::CreateLeague {
+            name,
+            format: format.unwrap_or_default(),
+            rounds,
+            max_players,
+            players: players.unwrap_or_default(),
+            private: private.unwrap_or(false),
+            match_format,
+            move_timeout_secs,
+            timeout_penalty,
+        });
+        message
+    }
+    
+    /// Register this chain for a league
+    async fn register_for_league(&self, league_id: String) -> String {
+        self.runtime.schedule_operation(&rock_paper_scissors::Operation::RegisterForLeague { league_id: league_id.clone() });
+        format!("Registration for league {} requested", league_id)
+    }
+    
+    /// Close registration and pair the first round of a league (admin operation)
+    async fn start_league(&self, league_id: String) -> String {
+        self.runtime.schedule_operation(&rock_paper_scissors::Operation::StartLeague { league_id: league_id.clone() });
+        format!("Start of league {} requested", league_id)
     }
     
-    /// Join an existing room
-    async fn join_room(&self, room_id: String) -> String {
-        self.runtime.schedule_operation(&rock_paper_scissors::Operation::JoinRoom { room_id: room_id.clone() });
+    /// Join an existing room, presenting the invite code of an invite-only room and paying
+    /// the room's entry fee if it has one
+    async fn join_room(&self, room_id: String, invite_code: Option<String>, entry_fee: Option<Amount>, currency: Option<Currency>) -> String {
+        self.runtime.schedule_operation(&rock_paper_scissors::Operation::JoinRoom { room_id: room_id.clone(), invite_code, entry_fee, currency });
         format!("Joined room: {}", room_id)
     }
     
-    /// Submit a choice for the current round
-    async fn submit_choice(&self, room_id: String, choice: Choice) -> String {
-        self.runtime.schedule_operation(&rock_paper_scissors::Operation::SubmitChoice { room_id: room_id.clone(), choice });
-        format!("Submitted choice {:?} for room: {}", choice, room_id)
+    /// Commit to a choice for the current round (see `choiceCommitment`)
+    async fn commit_choice(&self, room_id: String, commitment: String) -> String {
+        self.runtime.schedule_operation(&rock_paper_scissors::Operation::CommitChoice { room_id: room_id.clone(), commitment });
+        format!("Committed choice for room: {}", room_id)
+    }
+    
+    /// Reveal the committed choice once both players have committed
+    async fn reveal_choice(&self, room_id: String, choice: Choice, salt: String) -> String {
+        self.runtime.schedule_operation(&rock_paper_scissors::Operation::RevealChoice { room_id: room_id.clone(), choice, salt });
+        format!("Revealed choice {:?} for room: {}", choice, room_id)
+    }
+    
+    /// Claim the current round (or game) when the opponent has missed the move deadline
+    async fn claim_timeout(&self, room_id: String) -> String {
+        self.runtime.schedule_operation(&rock_paper_scissors::Operation::ClaimTimeout { room_id: room_id.clone() });
+        format!("Claimed timeout for room: {}", room_id)
+    }
+    
+    /// Leave a room before any choice was made; a leaving host cancels the room
+    async fn leave_room(&self, room_id: String) -> String {
+        self.runtime.schedule_operation(&rock_paper_scissors::Operation::LeaveRoom { room_id: room_id.clone() });
+        format!("Left room: {}", room_id)
+    }
+    
+    /// Concede an active match; the opponent wins the game
+    async fn resign(&self, room_id: String) -> String {
+        self.runtime.schedule_operation(&rock_paper_scissors::Operation::Resign { room_id: room_id.clone() });
+        format!("Resigned from room: {}", room_id)
+    }
+    
+    /// Offer the opponent a rematch of a finished room
+    async fn offer_rematch(&self, room_id: String) -> String {
+        self.runtime.schedule_operation(&rock_paper_scissors::Operation::OfferRematch { room_id: room_id.clone() });
+        format!("Offered rematch of room: {}", room_id)
+    }
+    
+    /// Accept the opponent's rematch offer
+    async fn accept_rematch(&self, room_id: String) -> String {
+        self.runtime.schedule_operation(&rock_paper_scissors::Operation::AcceptRematch { room_id: room_id.clone() });
+        format!("Accepted rematch of room: {}", room_id)
+    }
+    
+    /// Cancel a room this chain hosts, before both players have committed their first choice
+    async fn cancel_room(&self, room_id: String) -> String {
+        self.runtime.schedule_operation(&rock_paper_scissors::Operation::CancelRoom { room_id: room_id.clone() });
+        format!("Cancelled room: {}", room_id)
+    }
+    
+    /// Remove a player from a room this chain hosts, before any choice was made
+    async fn kick_player(&self, room_id: String, player: ChainId) -> String {
+        self.runtime.schedule_operation(&rock_paper_scissors::Operation::KickPlayer { room_id: room_id.clone(), player });
+        format!("Kicked player from room: {}", room_id)
+    }
+    
+    /// Wait for an opponent with a similar rating
+    async fn quick_match(&self) -> String {
+        self.runtime.schedule_operation(&rock_paper_scissors::Operation::QuickMatch);
+        "Joined matchmaking queue".to_string()
+    }
+    
+    /// Stop waiting for a quick match
+    async fn leave_queue(&self) -> String {
+        self.runtime.schedule_operation(&rock_paper_scissors::Operation::LeaveQueue);
+        "Left matchmaking queue".to_string()
     }
     
     /// Reset the leaderboard (admin operation, only on leaderboard chain)
@@ -302,9 +937,10 @@ impl MutationRoot {
     }
     
     /// Set player name
-    async fn set_player_name(&self, name: String) -> String {
+    async fn set_player_name(&self, name: String) -> async_graphql::Result<String> {
+        let name = validate_player_name(&name)?;
         self.runtime.schedule_operation(&rock_paper_scissors::Operation::SetPlayerName { name: name.clone() });
-        format!("Player name set to '{}' successfully", name)
+        Ok(format!("Player name set to '{}' successfully", name))
     }
 }
 
@@ -327,7 +963,14 @@ impl PlayerStats {
         }
     }
     
-    /// Get player rank based on wins (would need global context in real implementation)
+    /// Get the player's place on the global leaderboard, by wins unless another order is
+    /// given. Only known on the leaderboard chain.
+    async fn leaderboard_position(&self, ctx: &Context<'_>, order_by: Option<LeaderboardOrder>) -> async_graphql::Result<Option<LeaderboardPosition>> {
+        let state = ctx.data::<Arc<RockPaperScissorsState>>()?;
+        LeaderboardPosition::of(state, order_by.unwrap_or_default(), self.chain_id).await
+    }
+    
+    /// Get the player's skill tier based on wins; see `leaderboardPosition` for their actual place
     async fn estimated_rank(&self) -> String {
         if self.games_won >= 100 {
             "Master".to_string()
@@ -341,4 +984,76 @@ impl PlayerStats {
             "Beginner".to_string()
         }
     }
-}
\ No newline at end of file
+}
+#[cfg(test)]
+mod tests {
+    use super::*;
+    
+    /// Walk `keys` in order for one page
+    fn walk(keys: &[&str], first: i32, after: Option<&str>) -> Page<String> {
+        let mut page = Page::new(Some(first), after.map(str::to_string));
+        for key in keys {
+            if !page.visit(key.to_string()) {
+                break;
+            }
+        }
+        page
+    }
+    
+    #[test]
+    fn page_walk() {
+        let keys = ["a", "b", "c", "d", "e"];
+        let page = walk(&keys, 2, None);
+        assert_eq!(page.indices, ["a", "b"]);
+        assert!(!page.has_previous && page.has_next);
+        
+        let page = walk(&keys, 2, Some("b"));
+        assert_eq!(page.indices, ["c", "d"]);
+        assert!(page.has_previous && page.has_next);
+        
+        let page = walk(&keys, 2, Some("d"));
+        assert_eq!(page.indices, ["e"]);
+        assert!(page.has_previous && !page.has_next);
+        
+        assert!(walk(&keys, 0, None).indices.is_empty());
+    }
+    
+    #[test]
+    fn page_resumes_after_stale_cursor() {
+        // The cursor's item was removed before the next page was read
+        let page = walk(&["a", "c", "d", "e"], 2, Some("b"));
+        assert_eq!(page.indices, ["c", "d"]);
+        assert!(page.has_previous && page.has_next);
+        
+        // Items were added before the cursor
+        let page = walk(&["a", "aa", "ab", "b", "c"], 2, Some("b"));
+        assert_eq!(page.indices, ["c"]);
+        assert!(page.has_previous && !page.has_next);
+        
+        let page = walk(&["a", "b"], 2, Some("z"));
+        assert!(page.indices.is_empty());
+        assert!(page.has_previous && !page.has_next);
+    }
+    
+    #[test]
+    fn storage_key_order() {
+        let mut keys = vec!["10000-1", "0002-E", "0001-7", "zz"];
+        keys.sort_by_key(|key| storage_key(key.to_string()));
+        assert_eq!(keys, ["zz", "0001-7", "0002-E", "10000-1"]);
+    }
+    
+    #[test]
+    fn cursors() {
+        let room = GameRoom::new("0001-7".to_string(), 42, false, MatchFormat::default(), 60, TimeoutPenalty::default());
+        let cursor = room_cursor(&room);
+        assert_eq!(parse_room_cursor(&cursor).unwrap(), (42, "0001-7"));
+        for malformed in ["", "0001-7", "x:0001-7", "-1:0001-7"] {
+            assert!(parse_room_cursor(malformed).is_err(), "{}", malformed);
+        }
+        
+        let chain = format!("{:064x}", 1);
+        assert_eq!(chain_cursor(Some(chain.clone())).unwrap(), Some(chain));
+        assert_eq!(chain_cursor(None).unwrap(), None);
+        assert!(chain_cursor(Some("0001-7".to_string())).is_err());
+    }
+}
@@ -1,14 +1,18 @@
 // Copyright (c) Zefchain Labs, Inc.
 // SPDX-License-Identifier: Apache-2.0
 
-use linera_sdk::views::{linera_views, MapView, RegisterView, RootView, ViewStorageContext};
+use std::collections::VecDeque;
+
+use linera_sdk::views::{linera_views, MapView, RegisterView, RootView, ViewError, ViewStorageContext};
 use linera_sdk::linera_base_types::ChainId;
 use serde::{Deserialize, Serialize};
 use async_graphql::SimpleObject;
-use rock_paper_scissors::{GameRoom, LeaderboardEntry};
+use rock_paper_scissors::{glicko2::{Glicko2Config, Glicko2Rating}, league::League, rewards::RewardBalance, tournament::Tournament, GameRoom, LeaderboardEntry, LeaderboardOrder, QueueEntry, RejectedAction, RoomStatus,
+    Series, DEFAULT_ELO_RATING};
 
 /// Player statistics for tracking personal game history
 #[derive(Debug, Clone, Serialize, Deserialize, SimpleObject)]
+#[graphql(complex)]
 pub struct PlayerStats {
     pub chain_id: ChainId,
     pub games_played: u64,
@@ -17,6 +21,22 @@ pub struct PlayerStats {
     pub current_streak: u64,
     pub best_streak: u64,
     pub last_game_timestamp: u64,
+    pub elo_rating: i32,
+    pub glicko2: Glicko2Rating,
+    pub resignations: u64, // Games lost by resigning, also counted in games_lost
+    pub series_played: u64,
+    pub series_won: u64,
+}
+
+/// Elo rating change caused by one game
+#[derive(Debug, Clone, Serialize, Deserialize, SimpleObject)]
+pub struct RatingChange {
+    pub timestamp: u64,
+    pub room_id: String,
+    pub opponent: ChainId,
+    pub won: bool,
+    pub rating_before: i32,
+    pub rating_after: i32,
 }
 
 impl PlayerStats {
@@ -30,6 +50,11 @@ impl PlayerStats {
             current_streak: 0,
             best_streak: 0,
             last_game_timestamp: 0,
+            elo_rating: DEFAULT_ELO_RATING,
+            glicko2: Glicko2Rating::default(),
+            resignations: 0,
+            series_played: 0,
+            series_won: 0,
         }
     }
     
@@ -50,6 +75,14 @@ impl PlayerStats {
         }
     }
     
+    /// Glicko-2 rating at `now`, with deviation grown for the time since the last game
+    pub fn current_glicko2(&self, now: u64, config: &Glicko2Config) -> Glicko2Rating {
+        if self.games_played == 0 {
+            return self.glicko2;
+        }
+        self.glicko2.decayed(now.saturating_sub(self.last_game_timestamp), config)
+    }
+    
     pub fn win_rate(&self) -> f64 {
         if self.games_played > 0 {
             (self.games_won as f64) / (self.games_played as f64) * 100.0
@@ -66,15 +99,29 @@ pub struct RockPaperScissorsState {
     // Game state
     pub rooms: MapView<String, GameRoom>, // room_id -> GameRoom
     pub room_counter: RegisterView<u64>, // Counter for generating unique room IDs
+    pub room_aliases: MapView<String, String>, // alias -> room_id
     pub available_rooms: RegisterView<Vec<String>>, // List of rooms waiting for players
+    pub open_rooms: MapView<String, RoomStatus>, // room_id -> status of rooms that are not closed yet
+    pub matchmaking_queue: MapView<ChainId, QueueEntry>, // Players waiting for a quick match
+    pub series: MapView<String, Series>, // series_id -> Series
+    pub series_counter: RegisterView<u64>, // Counter for generating series IDs
+    pub tournaments: MapView<String, Tournament>, // tournament_id -> Tournament
+    pub tournament_counter: RegisterView<u64>, // Counter for generating tournament IDs
+    pub leagues: MapView<String, League>, // league_id -> League
+    pub league_counter: RegisterView<u64>, // Counter for generating league IDs
     
     // Player names
     pub player_names: MapView<ChainId, String>, // chain_id -> player_name
     pub my_player_name: RegisterView<Option<String>>, // This player's name
     
     // Leaderboard state (only on leaderboard chain)
-    pub global_leaderboard: RegisterView<Vec<LeaderboardEntry>>, // Top players globally
+    pub leaderboard_entries: MapView<ChainId, LeaderboardEntry>, // chain_id -> entry as currently indexed
+    pub leaderboard_count: RegisterView<u64>, // Number of players in leaderboard_entries
+    pub leaderboard_by_wins: MapView<String, LeaderboardEntry>, // wins rank key -> entry, best first
+    pub leaderboard_by_rating: MapView<String, LeaderboardEntry>, // rating rank key -> entry, best first
+    pub rating_history: MapView<ChainId, Vec<RatingChange>>, // chain_id -> recent rating changes
     pub player_stats: MapView<ChainId, PlayerStats>, // chain_id -> detailed stats
+    pub reward_balances: MapView<ChainId, RewardBalance>, // chain_id -> reward points for public wins
     pub is_leaderboard_chain: RegisterView<bool>, // Flag to identify if this is the leaderboard chain
     pub leaderboard_chain_id: RegisterView<Option<ChainId>>, // Store the leaderboard chain ID
     
@@ -82,4 +129,129 @@ pub struct RockPaperScissorsState {
     pub my_rooms: RegisterView<Vec<String>>, // Rooms this player is participating in
     pub my_stats: RegisterView<Option<PlayerStats>>, // Personal statistics
     pub my_current_room: RegisterView<Option<String>>, // Currently active room
-}
\ No newline at end of file
+    pub my_last_error: RegisterView<Option<RejectedAction>>, // Last request rejected by the leaderboard chain
+    pub my_queued: RegisterView<bool>, // Waiting in the matchmaking queue
+}
+
+impl RockPaperScissorsState {
+    /// Move a player to their new place in the leaderboard indexes, leaving everyone else in place
+    #[allow(dead_code)]
+    pub async fn index_leaderboard_entry(&mut self, entry: LeaderboardEntry) -> Result<(), ViewError> {
+        match self.leaderboard_entries.get(&entry.chain_id).await? {
+            Some(previous) => {
+                self.leaderboard_by_wins.remove(&previous.wins_rank_key())?;
+                self.leaderboard_by_rating.remove(&previous.rating_rank_key())?;
+            }
+            None => {
+                let count = *self.leaderboard_count.get() + 1;
+                self.leaderboard_count.set(count);
+            }
+        }
+        self.leaderboard_by_wins.insert(&entry.wins_rank_key(), entry.clone())?;
+        self.leaderboard_by_rating.insert(&entry.rating_rank_key(), entry.clone())?;
+        let chain_id = entry.chain_id;
+        self.leaderboard_entries.insert(&chain_id, entry)
+    }
+    
+    /// 1-based rank of each of `players` in `order`, None for players not on the leaderboard.
+    /// Walks the front of the index once, up to the lowest ranked of them.
+    #[allow(dead_code)]
+    pub async fn leaderboard_ranks(&self, order: LeaderboardOrder, players: &[ChainId]) -> Result<Vec<Option<u64>>, ViewError> {
+        let (index, rank_key): (_, fn(&LeaderboardEntry) -> String) = match order {
+            LeaderboardOrder::Wins => (&self.leaderboard_by_wins, LeaderboardEntry::wins_rank_key),
+            LeaderboardOrder::Rating => (&self.leaderboard_by_rating, LeaderboardEntry::rating_rank_key),
+        };
+        let mut keys = Vec::new();
+        for player in players {
+            keys.push(self.leaderboard_entries.get(player).await?.map(|entry| rank_key(&entry)));
+        }
+        
+        let mut ranks = vec![None; players.len()];
+        let mut remaining = keys.iter().flatten().count();
+        let mut position = 0;
+        if remaining > 0 {
+            index.for_each_index_while(|key| {
+                position += 1;
+                for (rank, _) in ranks.iter_mut().zip(&keys).filter(|(_, player_key)| player_key.as_ref() == Some(&key)) {
+                    *rank = Some(position);
+                    remaining -= 1;
+                }
+                Ok(remaining > 0)
+            }).await?;
+        }
+        Ok(ranks)
+    }
+    
+    /// Players ranked up to `radius` places above and below `player` in `order`, with their
+    /// 1-based ranks and current names. Empty when the player is not on the leaderboard.
+    #[allow(dead_code)]
+    pub async fn leaderboard_window(&self, order: LeaderboardOrder, player: ChainId, radius: usize) -> Result<Vec<(u64, LeaderboardEntry)>, ViewError> {
+        let (index, rank_key): (_, fn(&LeaderboardEntry) -> String) = match order {
+            LeaderboardOrder::Wins => (&self.leaderboard_by_wins, LeaderboardEntry::wins_rank_key),
+            LeaderboardOrder::Rating => (&self.leaderboard_by_rating, LeaderboardEntry::rating_rank_key),
+        };
+        let Some(player_key) = self.leaderboard_entries.get(&player).await?.map(|entry| rank_key(&entry)) else {
+            return Ok(Vec::new());
+        };
+        
+        // Keep the last `radius` keys seen until the player turns up, then take `radius` more
+        let mut window = VecDeque::new();
+        let mut position = 0;
+        let mut below = None;
+        index.for_each_index_while(|key| {
+            position += 1;
+            match below.as_mut() {
+                None => {
+                    if window.len() > radius {
+                        window.pop_front();
+                    }
+                    let found = key == player_key;
+                    window.push_back((position, key));
+                    if found {
+                        below = Some(0);
+                    }
+                }
+                Some(taken) => {
+                    window.push_back((position, key));
+                    *taken += 1;
+                }
+            }
+            Ok(below.is_none_or(|taken| taken < radius))
+        }).await?;
+        if below.is_none() {
+            return Ok(Vec::new());
+        }
+        
+        let mut entries = Vec::new();
+        for (rank, key) in window {
+            if let Some(mut entry) = index.get(&key).await? {
+                entry.player_name = self.player_names.get(&entry.chain_id).await?;
+                entries.push((rank, entry));
+            }
+        }
+        Ok(entries)
+    }
+    
+    /// The best `limit` players in `order`, read from the front of the index, with their
+    /// current names
+    #[allow(dead_code)]
+    pub async fn top_players(&self, order: LeaderboardOrder, limit: usize) -> Result<Vec<LeaderboardEntry>, ViewError> {
+        let index = match order {
+            LeaderboardOrder::Wins => &self.leaderboard_by_wins,
+            LeaderboardOrder::Rating => &self.leaderboard_by_rating,
+        };
+        let mut entries = Vec::new();
+        if limit == 0 {
+            return Ok(entries);
+        }
+        index.for_each_index_value_while(|_, entry| {
+            entries.push(entry.into_owned());
+            Ok(entries.len() < limit)
+        }).await?;
+        
+        for entry in &mut entries {
+            entry.player_name = self.player_names.get(&entry.chain_id).await?;
+        }
+        Ok(entries)
+    }
+}
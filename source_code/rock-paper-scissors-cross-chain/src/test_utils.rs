@@ -0,0 +1,33 @@
+// Copyright (c) Zefchain Labs, Inc.
+// SPDX-License-Identifier: Apache-2.0
+
+/*! Fixtures shared by the unit tests */
+
+use linera_sdk::linera_base_types::{AccountOwner, Amount, ChainId};
+
+use crate::{Currency, GameRoom, MatchFormat, Stake, TimeoutPenalty};
+
+/// Chain ID `n`, so chains sort by `n`
+pub fn chain(n: u8) -> ChainId {
+    format!("{:064x}", n).parse().unwrap()
+}
+
+/// Chains 1 to `count`
+pub fn chains(count: u8) -> Vec<ChainId> {
+    (1..=count).map(chain).collect()
+}
+
+/// Empty room with the default match format
+pub fn room(room_id: String, created_at: u64) -> GameRoom {
+    GameRoom::new(room_id, created_at, false, MatchFormat::default(), 60, TimeoutPenalty::default())
+}
+
+/// Entry fee of `tokens` native tokens paid by chain `n`
+pub fn stake(n: u8, tokens: u128) -> Stake {
+    Stake {
+        player: chain(n),
+        owner: AccountOwner::CHAIN,
+        amount: Amount::from_tokens(tokens),
+        currency: Currency::Native,
+    }
+}
@@ -0,0 +1,662 @@
+// Copyright (c) Zefchain Labs, Inc.
+// SPDX-License-Identifier: Apache-2.0
+
+/*! Single- and double-elimination tournament brackets
+
+A bracket is a flat list of matches. Each match knows where its winner and, in double
+elimination, its loser go next. Seats fill up as earlier matches finish; a match with only
+one player once all its seats are settled is a bye, and a match left with no players at all
+is skipped. Match IDs follow bracket order, so the last match that produced a winner decides
+the tournament.
+*/
+
+use async_graphql::{ComplexObject, Enum, SimpleObject};
+use linera_sdk::linera_base_types::{Amount, ChainId};
+use serde::{Deserialize, Serialize};
+use sha3::{Digest, Sha3_256};
+
+use crate::{check_stake, Currency, GameError, MatchFormat, Stake, TimeoutPenalty};
+
+/// Smallest field a tournament can start with
+pub const MIN_TOURNAMENT_PLAYERS: usize = 2;
+/// Largest field a tournament accepts
+pub const MAX_TOURNAMENT_PLAYERS: usize = 256;
+
+// Bracket type of a tournament
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq, Default, Enum)]
+pub enum TournamentFormat {
+    #[default]
+    SingleElimination, // One loss and a player is out
+    DoubleElimination, // Players drop to the losers bracket after their first loss
+}
+
+// How registered players are placed in the bracket
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq, Default, Enum)]
+pub enum SeedingMethod {
+    #[default]
+    Random, // Shuffled when the tournament starts
+    Rating, // Highest Elo rating gets the first seed
+}
+
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq, Enum)]
+pub enum TournamentStatus {
+    Registration, // Accepting players
+    InProgress,
+    Finished,
+    Cancelled, // Closed before it started, entry fees refunded
+}
+
+// Part of the bracket a match belongs to
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq, Enum)]
+pub enum BracketSide {
+    Winners,
+    Losers,
+    GrandFinal, // Round 1 is the grand final, round 2 the reset if the losers bracket wins it
+}
+
+// Seat of a match that a player advances to
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq, SimpleObject)]
+pub struct BracketSlot {
+    pub match_id: u32,
+    pub seat: u8, // 1 or 2
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, SimpleObject)]
+pub struct BracketMatch {
+    pub match_id: u32,
+    pub side: BracketSide,
+    pub round: u32,
+    pub player1: Option<ChainId>,
+    pub player2: Option<ChainId>,
+    pub awaiting: u8, // Seats still waiting for an earlier match
+    pub room_id: Option<String>, // Room of the current game, replaced when a drawn game is replayed
+    pub winner: Option<ChainId>,
+    pub bye: bool, // Decided without a game
+    pub finished: bool,
+    pub winner_to: Option<BracketSlot>,
+    pub loser_to: Option<BracketSlot>,
+}
+
+// Matches of one bracket round, for the GraphQL bracket view
+#[derive(Debug, Clone, SimpleObject)]
+pub struct BracketRound {
+    pub side: BracketSide,
+    pub round: u32,
+    pub matches: Vec<BracketMatch>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, SimpleObject)]
+#[graphql(complex)]
+pub struct Tournament {
+    pub tournament_id: String,
+    pub name: String,
+    pub format: TournamentFormat,
+    pub seeding: SeedingMethod,
+    pub status: TournamentStatus,
+    pub max_players: u32,
+    pub players: Vec<ChainId>, // Registered players, in registration order
+    pub seeds: Vec<ChainId>, // Players by seed once started, first seed first
+    pub matches: Vec<BracketMatch>,
+    pub champion: Option<ChainId>,
+    pub private: bool, // Games are private rooms and not recorded on the leaderboard
+    pub match_format: MatchFormat, // Format of each game
+    pub move_timeout_secs: u64,
+    pub timeout_penalty: TimeoutPenalty,
+    pub created_at: u64,
+    pub entry_fee: Amount, // Tokens paid on registration
+    pub currency: Currency, // What the entry fee and the prize pool are paid in
+    pub prize_pool: Amount, // Entry fees held in escrow, paid to the champion
+    #[graphql(skip)]
+    pub stakes: Vec<Stake>, // Entry fees not yet paid out
+}
+
+#[ComplexObject]
+impl Tournament {
+    /// Matches grouped by bracket side and round, winners bracket first
+    async fn bracket(&self, side: Option<BracketSide>) -> Vec<BracketRound> {
+        let mut rounds: Vec<BracketRound> = Vec::new();
+        for bracket_match in self.matches.iter().filter(|m| side.is_none_or(|side| m.side == side)) {
+            match rounds.last_mut() {
+                Some(round) if round.side == bracket_match.side && round.round == bracket_match.round => {
+                    round.matches.push(bracket_match.clone());
+                }
+                _ => rounds.push(BracketRound {
+                    side: bracket_match.side,
+                    round: bracket_match.round,
+                    matches: vec![bracket_match.clone()],
+                }),
+            }
+        }
+        rounds
+    }
+}
+
+impl BracketMatch {
+    fn new(match_id: u32, side: BracketSide, round: u32, awaiting: u8) -> Self {
+        Self {
+            match_id,
+            side,
+            round,
+            player1: None,
+            player2: None,
+            awaiting,
+            room_id: None,
+            winner: None,
+            bye: false,
+            finished: false,
+            winner_to: None,
+            loser_to: None,
+        }
+    }
+    
+    /// Returns true once both seats are settled and two players need to play
+    pub fn is_playable(&self) -> bool {
+        !self.finished && self.awaiting == 0 && self.player1.is_some() && self.player2.is_some()
+    }
+}
+
+impl Tournament {
+    #[allow(clippy::too_many_arguments)]
+    pub fn new(
+        tournament_id: String,
+        name: String,
+        format: TournamentFormat,
+        seeding: SeedingMethod,
+        max_players: u32,
+        private: bool,
+        match_format: MatchFormat,
+        move_timeout_secs: u64,
+        timeout_penalty: TimeoutPenalty,
+        created_at: u64,
+    ) -> Self {
+        Self {
+            tournament_id,
+            name,
+            format,
+            seeding,
+            status: TournamentStatus::Registration,
+            max_players,
+            players: Vec::new(),
+            seeds: Vec::new(),
+            matches: Vec::new(),
+            champion: None,
+            private,
+            match_format,
+            move_timeout_secs,
+            timeout_penalty,
+            created_at,
+            entry_fee: Amount::ZERO,
+            currency: Currency::Native,
+            prize_pool: Amount::ZERO,
+            stakes: Vec::new(),
+        }
+    }
+    
+    pub fn register(&mut self, player: ChainId) -> Result<(), GameError> {
+        if self.status != TournamentStatus::Registration {
+            return Err(GameError::TournamentNotOpen);
+        }
+        if self.players.contains(&player) {
+            return Err(GameError::AlreadyRegistered);
+        }
+        if self.players.len() >= self.max_players as usize {
+            return Err(GameError::TournamentFull);
+        }
+        self.players.push(player);
+        Ok(())
+    }
+    
+    /// Register a player who paid the entry fee, adding it to the prize pool
+    pub fn register_paid(&mut self, player: ChainId, stake: Option<Stake>) -> Result<(), GameError> {
+        check_stake(stake.as_ref(), self.entry_fee, self.currency)?;
+        self.register(player)?;
+        if let Some(stake) = stake {
+            self.prize_pool = self.prize_pool.saturating_add(stake.amount);
+            self.stakes.push(stake);
+        }
+        Ok(())
+    }
+    
+    /// Close registration and build the bracket from `seeds`, first seed first. Returns the
+    /// IDs of the matches ready to be played.
+    pub fn start(&mut self, seeds: Vec<ChainId>) -> Result<Vec<u32>, GameError> {
+        if self.status != TournamentStatus::Registration {
+            return Err(GameError::TournamentNotOpen);
+        }
+        if seeds.len() < MIN_TOURNAMENT_PLAYERS {
+            return Err(GameError::NotEnoughPlayers);
+        }
+        
+        self.matches = build_bracket(&seeds, self.format);
+        self.seeds = seeds;
+        self.status = TournamentStatus::InProgress;
+        Ok(self.resolve())
+    }
+    
+    /// Close a tournament that has not started, returning the entry fees to refund
+    pub fn cancel(&mut self) -> Result<Vec<Stake>, GameError> {
+        if self.status != TournamentStatus::Registration {
+            return Err(GameError::TournamentNotOpen);
+        }
+        self.status = TournamentStatus::Cancelled;
+        self.prize_pool = Amount::ZERO;
+        Ok(std::mem::take(&mut self.stakes))
+    }
+    
+    /// Match currently played in `room_id`
+    pub fn match_in_room(&self, room_id: &str) -> Option<u32> {
+        self.matches.iter()
+            .find(|m| !m.finished && m.room_id.as_deref() == Some(room_id))
+            .map(|m| m.match_id)
+    }
+    
+    /// Record the winner of a played match and advance both players. Returns the IDs of
+    /// the matches that became ready to be played.
+    pub fn record_result(&mut self, match_id: u32, winner: ChainId) -> Result<Vec<u32>, GameError> {
+        let bracket_match = self.matches.get(match_id as usize).ok_or(GameError::InvalidRoomState)?;
+        if !bracket_match.is_playable() {
+            return Err(GameError::InvalidRoomState);
+        }
+        if bracket_match.player1 != Some(winner) && bracket_match.player2 != Some(winner) {
+            return Err(GameError::NotAPlayer);
+        }
+        
+        self.finish_match(match_id, Some(winner));
+        Ok(self.resolve())
+    }
+    
+    /// Settle byes and empty matches until only playable or finished matches are left
+    fn resolve(&mut self) -> Vec<u32> {
+        loop {
+            let settled = self.matches.iter()
+                .find(|m| !m.finished && m.awaiting == 0 && (m.player1.is_none() || m.player2.is_none()))
+                .map(|m| (m.match_id, m.player1.or(m.player2)));
+            let Some((match_id, winner)) = settled else {
+                break;
+            };
+            self.matches[match_id as usize].bye = winner.is_some();
+            self.finish_match(match_id, winner);
+        }
+        
+        if self.matches.iter().all(|m| m.finished) {
+            self.status = TournamentStatus::Finished;
+            self.champion = self.matches.iter().rev().find_map(|m| m.winner);
+        }
+        self.matches.iter()
+            .filter(|m| m.is_playable() && m.room_id.is_none())
+            .map(|m| m.match_id)
+            .collect()
+    }
+    
+    /// Mark a match as finished and send its winner and loser on. `winner` is `None` for a
+    /// match that was left without players.
+    fn finish_match(&mut self, match_id: u32, winner: Option<ChainId>) {
+        let bracket_match = &mut self.matches[match_id as usize];
+        bracket_match.finished = true;
+        bracket_match.winner = winner;
+        let loser = if winner.is_some() && bracket_match.player1 == winner {
+            bracket_match.player2
+        } else {
+            bracket_match.player1.filter(|_| winner.is_some())
+        };
+        let (winner_to, loser_to) = (bracket_match.winner_to, bracket_match.loser_to);
+        
+        // The grand final is only replayed if the losers bracket champion wins it
+        if bracket_match.side == BracketSide::GrandFinal && bracket_match.round == 1 {
+            let winners_champion = bracket_match.player1;
+            if let Some(reset) = self.matches.get_mut(match_id as usize + 1) {
+                reset.awaiting = 0;
+                if winner.is_some() && winner != winners_champion {
+                    reset.player1 = winners_champion;
+                    reset.player2 = winner;
+                }
+            }
+            return;
+        }
+        
+        for (player, slot) in [(winner, winner_to), (loser, loser_to)] {
+            if let Some(slot) = slot {
+                let next = &mut self.matches[slot.match_id as usize];
+                if slot.seat == 1 {
+                    next.player1 = player;
+                } else {
+                    next.player2 = player;
+                }
+                next.awaiting -= 1;
+            }
+        }
+    }
+}
+
+/// Order players for random seeding. Contracts have no source of randomness, so players
+/// are shuffled by a hash of `salt` and their chain ID.
+pub fn shuffle_seeds(players: &[ChainId], salt: &str) -> Vec<ChainId> {
+    let mut seeds = players.to_vec();
+    seeds.sort_by_cached_key(|player| {
+        Sha3_256::digest(format!("seed:{}:{}", salt, player).as_bytes()).to_vec()
+    });
+    seeds
+}
+
+/// Seed numbers in bracket order for a bracket of `size` players, so that the top seeds
+/// can only meet in the late rounds: 1 v 8, 4 v 5, 2 v 7, 3 v 6 for eight players
+fn seed_order(size: usize) -> Vec<usize> {
+    let mut order = vec![1];
+    while order.len() < size {
+        let len = order.len();
+        order = order.into_iter().flat_map(|seed| [seed, 2 * len + 1 - seed]).collect();
+    }
+    order
+}
+
+/// Build the matches of a bracket. Fields that are not a power of two are padded with
+/// byes, which go to the top seeds.
+fn build_bracket(seeds: &[ChainId], format: TournamentFormat) -> Vec<BracketMatch> {
+    let size = seeds.len().next_power_of_two();
+    let rounds = size.trailing_zeros();
+    let mut matches: Vec<BracketMatch> = Vec::new();
+    
+    // Winners bracket, one list of match IDs per round
+    let mut winners_rounds: Vec<Vec<u32>> = Vec::new();
+    for round in 1..=rounds {
+        let count = size >> round;
+        let awaiting = if round == 1 { 0 } else { 2 };
+        let ids: Vec<u32> = (0..count)
+            .map(|_| {
+                let match_id = matches.len() as u32;
+                matches.push(BracketMatch::new(match_id, BracketSide::Winners, round, awaiting));
+                match_id
+            })
+            .collect();
+        if let Some(previous) = winners_rounds.last() {
+            for (index, &from) in previous.iter().enumerate() {
+                matches[from as usize].winner_to = Some(BracketSlot { match_id: ids[index / 2], seat: index as u8 % 2 + 1 });
+            }
+        }
+        winners_rounds.push(ids);
+    }
+    
+    let order = seed_order(size);
+    for (index, &match_id) in winners_rounds[0].iter().enumerate() {
+        let first_round = &mut matches[match_id as usize];
+        first_round.player1 = seeds.get(order[2 * index] - 1).copied();
+        first_round.player2 = seeds.get(order[2 * index + 1] - 1).copied();
+    }
+    
+    if format == TournamentFormat::SingleElimination {
+        return matches;
+    }
+    
+    // Losers bracket: odd rounds pair up survivors, even rounds take in the losers of the
+    // next winners bracket round, in reverse order to avoid early rematches
+    let mut survivors: Vec<u32> = Vec::new();
+    for round in 1..rounds.saturating_sub(1) * 2 + 1 {
+        let ids: Vec<u32> = (0..size >> (round.div_ceil(2) + 1))
+            .map(|_| {
+                let match_id = matches.len() as u32;
+                matches.push(BracketMatch::new(match_id, BracketSide::Losers, round, 2));
+                match_id
+            })
+            .collect();
+        
+        if round == 1 {
+            for (index, &from) in winners_rounds[0].iter().enumerate() {
+                matches[from as usize].loser_to = Some(BracketSlot { match_id: ids[index / 2], seat: index as u8 % 2 + 1 });
+            }
+        } else if round % 2 == 0 {
+            let dropping = &winners_rounds[(round / 2) as usize];
+            for (index, &to) in ids.iter().enumerate() {
+                matches[survivors[index] as usize].winner_to = Some(BracketSlot { match_id: to, seat: 1 });
+                let from = dropping[dropping.len() - 1 - index];
+                matches[from as usize].loser_to = Some(BracketSlot { match_id: to, seat: 2 });
+            }
+        } else {
+            for (index, &from) in survivors.iter().enumerate() {
+                matches[from as usize].winner_to = Some(BracketSlot { match_id: ids[index / 2], seat: index as u8 % 2 + 1 });
+            }
+        }
+        survivors = ids;
+    }
+    
+    // Grand final between both bracket champions, followed by the reset match
+    let grand_final = matches.len() as u32;
+    matches.push(BracketMatch::new(grand_final, BracketSide::GrandFinal, 1, 2));
+    matches.push(BracketMatch::new(grand_final + 1, BracketSide::GrandFinal, 2, 1));
+    
+    let winners_final = winners_rounds[rounds as usize - 1][0];
+    matches[winners_final as usize].winner_to = Some(BracketSlot { match_id: grand_final, seat: 1 });
+    match survivors.first() {
+        Some(&losers_final) => {
+            matches[losers_final as usize].winner_to = Some(BracketSlot { match_id: grand_final, seat: 2 });
+        }
+        // With two players the loser of the only winners bracket match goes straight to the grand final
+        None => {
+            matches[winners_final as usize].loser_to = Some(BracketSlot { match_id: grand_final, seat: 2 });
+        }
+    }
+    matches
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_utils::{chain, chains, stake};
+    
+    fn tournament(format: TournamentFormat) -> Tournament {
+        Tournament::new(
+            "T1".to_string(),
+            "Test".to_string(),
+            format,
+            SeedingMethod::Rating,
+            MAX_TOURNAMENT_PLAYERS as u32,
+            false,
+            MatchFormat::default(),
+            60,
+            TimeoutPenalty::default(),
+            0,
+        )
+    }
+    
+    fn started(players: u8, format: TournamentFormat) -> (Tournament, Vec<u32>) {
+        let mut tournament = tournament(format);
+        let ready = tournament.start(chains(players)).unwrap();
+        (tournament, ready)
+    }
+    
+    /// Play every ready match with `pick` choosing the winner, returning the games played
+    /// as (match ID, winner, loser)
+    fn play(
+        tournament: &mut Tournament,
+        mut ready: Vec<u32>,
+        pick: impl Fn(&BracketMatch) -> ChainId,
+    ) -> Vec<(u32, ChainId, ChainId)> {
+        let mut games = Vec::new();
+        open_rooms(tournament, &ready);
+        while let Some(match_id) = ready.pop() {
+            let bracket_match = &tournament.matches[match_id as usize];
+            let (player1, player2) = (bracket_match.player1.unwrap(), bracket_match.player2.unwrap());
+            let winner = pick(bracket_match);
+            let loser = if winner == player1 { player2 } else { player1 };
+            games.push((match_id, winner, loser));
+            let newly_ready = tournament.record_result(match_id, winner).unwrap();
+            open_rooms(tournament, &newly_ready);
+            ready.extend(newly_ready);
+        }
+        games
+    }
+    
+    /// Give ready matches a room, as the contract does, so they are not reported again
+    fn open_rooms(tournament: &mut Tournament, match_ids: &[u32]) {
+        for &match_id in match_ids {
+            tournament.matches[match_id as usize].room_id = Some(format!("room-{}", match_id));
+        }
+    }
+    
+    fn seed(tournament: &Tournament, player: Option<ChainId>) -> usize {
+        tournament.seeds.iter().position(|&seed| Some(seed) == player).unwrap() + 1
+    }
+    
+    fn favourite(tournament: &Tournament) -> impl Fn(&BracketMatch) -> ChainId + '_ {
+        |m| if seed(tournament, m.player1) < seed(tournament, m.player2) { m.player1.unwrap() } else { m.player2.unwrap() }
+    }
+    
+    fn underdog(tournament: &Tournament) -> impl Fn(&BracketMatch) -> ChainId + '_ {
+        |m| if seed(tournament, m.player1) > seed(tournament, m.player2) { m.player1.unwrap() } else { m.player2.unwrap() }
+    }
+    
+    fn losses(games: &[(u32, ChainId, ChainId)], player: ChainId) -> usize {
+        games.iter().filter(|(_, _, loser)| *loser == player).count()
+    }
+    
+    #[test]
+    fn seed_order_keeps_top_seeds_apart() {
+        assert_eq!(seed_order(1), vec![1]);
+        assert_eq!(seed_order(2), vec![1, 2]);
+        assert_eq!(seed_order(4), vec![1, 4, 2, 3]);
+        assert_eq!(seed_order(8), vec![1, 8, 4, 5, 2, 7, 3, 6]);
+    }
+    
+    #[test]
+    fn single_elimination() {
+        for players in [2, 3, 5, 8] {
+            for favourites_win in [true, false] {
+                let (mut tournament, ready) = started(players, TournamentFormat::SingleElimination);
+                let snapshot = tournament.clone();
+                let games = if favourites_win {
+                    play(&mut tournament, ready, favourite(&snapshot))
+                } else {
+                    play(&mut tournament, ready, underdog(&snapshot))
+                };
+                
+                assert_eq!(tournament.status, TournamentStatus::Finished);
+                assert_eq!(games.len(), usize::from(players) - 1, "{} players", players);
+                let champion = tournament.champion.unwrap();
+                for player in chains(players) {
+                    assert_eq!(losses(&games, player), usize::from(player != champion));
+                }
+                if favourites_win {
+                    assert_eq!(champion, chain(1));
+                }
+            }
+        }
+    }
+    
+    #[test]
+    fn byes_go_to_top_seeds() {
+        let (tournament, ready) = started(5, TournamentFormat::SingleElimination);
+        let byes: Vec<usize> = tournament.matches.iter()
+            .filter(|m| m.bye)
+            .map(|m| seed(&tournament, m.winner))
+            .collect();
+        assert_eq!(byes, vec![1, 2, 3]);
+        
+        // 4 v 5 is the only first round game, and 2 v 3 can start right away after their byes
+        let pairings: Vec<(u32, usize, usize)> = ready.iter()
+            .map(|&match_id| &tournament.matches[match_id as usize])
+            .map(|m| (m.round, seed(&tournament, m.player1), seed(&tournament, m.player2)))
+            .collect();
+        assert_eq!(pairings, vec![(1, 4, 5), (2, 2, 3)]);
+    }
+    
+    #[test]
+    fn double_elimination() {
+        for players in [2, 3, 5, 8] {
+            for favourites_win in [true, false] {
+                let (mut tournament, ready) = started(players, TournamentFormat::DoubleElimination);
+                let snapshot = tournament.clone();
+                let games = if favourites_win {
+                    play(&mut tournament, ready, favourite(&snapshot))
+                } else {
+                    play(&mut tournament, ready, underdog(&snapshot))
+                };
+                
+                assert_eq!(tournament.status, TournamentStatus::Finished);
+                let champion = tournament.champion.unwrap();
+                for player in chains(players) {
+                    let expected = if player == champion { 0..=1 } else { 2..=2 };
+                    assert!(expected.contains(&losses(&games, player)), "{} players, {:?}", players, player);
+                }
+                
+                // A first loss drops a player to the losers bracket, not out
+                for (match_id, _, loser) in &games {
+                    let bracket_match = &tournament.matches[*match_id as usize];
+                    if bracket_match.side == BracketSide::Winners {
+                        assert!(tournament.matches.iter().any(|m| m.side != BracketSide::Winners
+                            && (m.player1 == Some(*loser) || m.player2 == Some(*loser))));
+                    }
+                }
+                if favourites_win {
+                    assert_eq!(champion, chain(1));
+                }
+            }
+        }
+    }
+    
+    #[test]
+    fn grand_final_without_reset() {
+        let (mut tournament, ready) = started(2, TournamentFormat::DoubleElimination);
+        let snapshot = tournament.clone();
+        let games = play(&mut tournament, ready, favourite(&snapshot));
+        
+        // Winners final, then the grand final won by the winners bracket champion
+        assert_eq!(games.len(), 2);
+        let reset = tournament.matches.last().unwrap();
+        assert_eq!((reset.side, reset.round), (BracketSide::GrandFinal, 2));
+        assert!(reset.finished && reset.winner.is_none() && !reset.bye);
+        assert_eq!(tournament.champion, Some(chain(1)));
+    }
+    
+    #[test]
+    fn grand_final_reset() {
+        for players in [2, 3, 5, 8] {
+            let (mut tournament, ready) = started(players, TournamentFormat::DoubleElimination);
+            let snapshot = tournament.clone();
+            let grand_final = tournament.matches.len() as u32 - 2;
+            
+            // The top seed wins everything but the grand final, then takes the reset
+            let games = play(&mut tournament, ready, |m| {
+                if m.match_id == grand_final {
+                    underdog(&snapshot)(m)
+                } else {
+                    favourite(&snapshot)(m)
+                }
+            });
+            
+            let reset = tournament.matches.last().unwrap();
+            assert_eq!(reset.player1, Some(chain(1)));
+            assert_eq!(reset.player2, Some(chain(2)));
+            assert_eq!(reset.winner, Some(chain(1)));
+            assert_eq!(games.last().unwrap().0, reset.match_id);
+            assert_eq!(tournament.champion, Some(chain(1)));
+            assert_eq!(losses(&games, chain(1)), 1);
+        }
+    }
+    
+    #[test]
+    fn rejects_results_for_unplayable_matches() {
+        let (mut tournament, ready) = started(3, TournamentFormat::SingleElimination);
+        let final_match = tournament.matches.len() as u32 - 1;
+        assert_eq!(tournament.record_result(final_match, chain(1)), Err(GameError::InvalidRoomState));
+        assert_eq!(tournament.record_result(ready[0], chain(1)), Err(GameError::NotAPlayer));
+    }
+    
+    #[test]
+    fn cancel_refunds_entry_fees() {
+        let mut tournament = tournament(TournamentFormat::SingleElimination);
+        tournament.entry_fee = Amount::from_tokens(2);
+        tournament.register_paid(chain(1), Some(stake(1, 2))).unwrap();
+        assert_eq!(tournament.start(vec![chain(1)]), Err(GameError::NotEnoughPlayers));
+        tournament.register_paid(chain(2), Some(stake(2, 2))).unwrap();
+        assert_eq!(tournament.prize_pool, Amount::from_tokens(4));
+        
+        assert_eq!(tournament.cancel(), Ok(vec![stake(1, 2), stake(2, 2)]));
+        assert_eq!(tournament.status, TournamentStatus::Cancelled);
+        assert_eq!(tournament.prize_pool, Amount::ZERO);
+        assert_eq!(tournament.cancel(), Err(GameError::TournamentNotOpen));
+        assert_eq!(tournament.register_paid(chain(3), Some(stake(3, 2))), Err(GameError::TournamentNotOpen));
+        assert_eq!(tournament.start(vec![chain(1), chain(2)]), Err(GameError::TournamentNotOpen));
+        
+        let (mut tournament, _) = started(2, TournamentFormat::SingleElimination);
+        assert_eq!(tournament.cancel(), Err(GameError::TournamentNotOpen));
+    }
+}